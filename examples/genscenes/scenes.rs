@@ -1,22 +1,26 @@
 use std::process;
 
+use rand::SeedableRng;
 use rtiaw::{
+    RenderRng,
     camera::{Camera, render},
     color::Color,
     hittable_list::HittableList,
-    material::Mat,
+    material::{Dielectric, Lambertain, Mat, Metal},
     rand_f64, rand_range_f64,
-    sphere::Hittable,
+    sphere::Sphere,
     vec3::{Point3, Vec3},
 };
 
 pub fn scene1() {
-    let mut world = HittableList::<104>::new();
+    // Fixed seed so the layout is the same on every run
+    let mut rng = RenderRng::seed_from_u64(1);
+    let mut world = HittableList::new();
 
-    let ground_material = Mat::Lambertain {
-        albedo: Color::new(0.5, 0.5, 0.5),
-    };
-    world.add(Hittable::Sphere(
+    let ground_material = Mat::Lambertain(Lambertain {
+        albedo: Color::new(0.5, 0.5, 0.5).into(),
+    });
+    world.add(Sphere::new(
         Vec3 {
             e: [0.0, -1000.0, 0.0],
         },
@@ -26,42 +30,47 @@ pub fn scene1() {
 
     for a in -5..5 {
         for b in -5..5 {
-            let choose_mat = rand_f64();
+            let choose_mat = rand_f64(&mut rng);
             let center = Point3::new(
-                0.9f64.mul_add(rand_f64(), f64::from(a)),
+                0.9f64.mul_add(rand_f64(&mut rng), f64::from(a)),
                 0.2,
-                0.9f64.mul_add(rand_f64(), f64::from(b)),
+                0.9f64.mul_add(rand_f64(&mut rng), f64::from(b)),
             );
 
             if (center - Point3::new(4.0, 0.2, 0.0)).len() > 0.9 {
                 if choose_mat < 0.8 {
-                    let albedo = Color::random() * Color::random();
-                    let sphere_mat = Mat::Lambertain { albedo };
-                    world.add(Hittable::Sphere(center, 0.2, sphere_mat));
+                    let albedo = Color::random(&mut rng) * Color::random(&mut rng);
+                    let sphere_mat = Mat::Lambertain(Lambertain {
+                        albedo: albedo.into(),
+                    });
+                    world.add(Sphere::new(center, 0.2, sphere_mat));
                 } else if choose_mat < 0.95 {
-                    let albedo = Color::random_rng(0.5, 1.0);
-                    let fuzz = rand_range_f64(0.0, 0.5);
-                    let sphere_mat = Mat::Metal { albedo, fuzz };
-                    world.add(Hittable::Sphere(center, 0.2, sphere_mat));
+                    let albedo = Color::random_rng(&mut rng, 0.5, 1.0);
+                    let fuzz = rand_range_f64(&mut rng, 0.0, 0.5);
+                    let sphere_mat = Mat::Metal(Metal {
+                        albedo: albedo.into(),
+                        fuzz,
+                    });
+                    world.add(Sphere::new(center, 0.2, sphere_mat));
                 } else {
-                    let sphere_mat = Mat::Dielectric {
+                    let sphere_mat = Mat::Dielectric(Dielectric {
                         refraction_index: 1.5,
-                    };
-                    world.add(Hittable::Sphere(center, 0.2, sphere_mat));
+                    });
+                    world.add(Sphere::new(center, 0.2, sphere_mat));
                 }
             }
         }
     }
 
-    let mat1 = Mat::Dielectric {
+    let mat1 = Mat::Dielectric(Dielectric {
         refraction_index: 1.5,
-    };
-    world.add(Hittable::Sphere(Vec3 { e: [0.0, 1.0, 0.0] }, 1.0, mat1));
+    });
+    world.add(Sphere::new(Vec3 { e: [0.0, 1.0, 0.0] }, 1.0, mat1));
 
-    let mat2 = Mat::Lambertain {
-        albedo: Color::new(0.4, 0.2, 0.1),
-    };
-    world.add(Hittable::Sphere(
+    let mat2 = Mat::Lambertain(Lambertain {
+        albedo: Color::new(0.4, 0.2, 0.1).into(),
+    });
+    world.add(Sphere::new(
         Vec3 {
             e: [-4.0, 1.0, 0.0],
         },
@@ -69,11 +78,11 @@ pub fn scene1() {
         mat2,
     ));
 
-    let mat3 = Mat::Metal {
-        albedo: Color::new(0.7, 0.6, 0.5),
+    let mat3 = Mat::Metal(Metal {
+        albedo: Color::new(0.7, 0.6, 0.5).into(),
         fuzz: 0.0,
-    };
-    world.add(Hittable::Sphere(Vec3 { e: [4.0, 1.0, 0.0] }, 1.0, mat3));
+    });
+    world.add(Sphere::new(Vec3 { e: [4.0, 1.0, 0.0] }, 1.0, mat3));
     let mut cam = Camera::default();
     cam.aspect_ratio = 16.0 / 9.0;
     cam.image_width = 1200;
@@ -83,47 +92,47 @@ pub fn scene1() {
     cam.look_from = Point3::new(13.0, 2.0, 3.0);
     cam.look_at = Point3::new(0.0, 0.0, 0.0);
     cam.vup = Vec3::new(0.0, 1.0, 0.0);
-    if let Err(e) = render(&mut cam, "scene1.ppm", &world) {
-        println!("Encountered Error: {e:?}");
+    if let Err(e) = render(&cam, "scene1.ppm", &world) {
+        println!("Encountered Error: {e}");
         process::exit(1);
     }
 }
 
 pub fn scene2() {
-    let mut world = HittableList::<4>::new();
-    world.add(Hittable::Sphere(
+    let mut world = HittableList::with_capacity(4);
+    world.add(Sphere::new(
         Vec3 {
             e: [0.0, -30.0, 0.0],
         },
         30.0,
-        Mat::Lambertain {
-            albedo: Color::new(0.2, 0.2, 0.2),
-        },
+        Mat::Lambertain(Lambertain {
+            albedo: Color::new(0.2, 0.2, 0.2).into(),
+        }),
     ));
 
-    world.add(Hittable::Sphere(
+    world.add(Sphere::new(
         Vec3 { e: [0.0, 0.3, 0.0] },
         0.3,
-        Mat::Metal {
-            albedo: Color::new(0.7, 0.4, 0.5),
+        Mat::Metal(Metal {
+            albedo: Color::new(0.7, 0.4, 0.5).into(),
             fuzz: 0.0,
-        },
+        }),
     ));
-    world.add(Hittable::Sphere(
+    world.add(Sphere::new(
         Vec3 { e: [0.6, 0.3, 0.0] },
         0.3,
-        Mat::Lambertain {
-            albedo: Color::new(0.2, 0.5, 0.2),
-        },
+        Mat::Lambertain(Lambertain {
+            albedo: Color::new(0.2, 0.5, 0.2).into(),
+        }),
     ));
-    world.add(Hittable::Sphere(
+    world.add(Sphere::new(
         Vec3 {
             e: [-0.6, 0.3, 0.0],
         },
         0.3,
-        Mat::Dielectric {
+        Mat::Dielectric(Dielectric {
             refraction_index: 1.5,
-        },
+        }),
     ));
     let mut cam = Camera::default();
     cam.image_width = 2560;
@@ -134,8 +143,8 @@ pub fn scene2() {
     cam.look_at = Point3::new(0.0, 0.3, 0.0);
     cam.vfov = 15.0;
     cam.vup = Vec3::new(0.0, 1.0, 0.0);
-    if let Err(e) = render(&mut cam, "scene2.ppm", &world) {
-        println!("Encountered Error: {e:?}");
+    if let Err(e) = render(&cam, "scene2.ppm", &world) {
+        println!("Encountered Error: {e}");
         process::exit(1);
     }
 }
@@ -0,0 +1,137 @@
+use crate::{
+    internal::Interval,
+    ray::Ray,
+    vec3::Point3,
+};
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Aabb {
+    #[must_use]
+    pub const fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Box spanning the two corner points, in any order
+    #[must_use]
+    pub const fn from_points(a: &Point3, b: &Point3) -> Self {
+        Self {
+            x: Interval::new(a.e[0].min(b.e[0]), &a.e[0].max(b.e[0])),
+            y: Interval::new(a.e[1].min(b.e[1]), &a.e[1].max(b.e[1])),
+            z: Interval::new(a.e[2].min(b.e[2]), &a.e[2].max(b.e[2])),
+        }
+    }
+
+    /// Smallest box enclosing both `a` and `b`
+    #[must_use]
+    pub const fn surrounding(a: &Self, b: &Self) -> Self {
+        Self {
+            x: Interval::enclosing(&a.x, &b.x),
+            y: Interval::enclosing(&a.y, &b.y),
+            z: Interval::enclosing(&a.z, &b.z),
+        }
+    }
+
+    /// Pads any side thinner than a small delta, so planar objects still have a volume to hit
+    #[must_use]
+    pub fn pad_to_minimums(&self) -> Self {
+        let delta = 0.0001;
+        let pad = |i: &Interval| if i.size() < delta { i.expand(delta) } else { *i };
+        Self {
+            x: pad(&self.x),
+            y: pad(&self.y),
+            z: pad(&self.z),
+        }
+    }
+
+    #[must_use]
+    pub const fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.x.min > self.x.max || self.y.min > self.y.max || self.z.min > self.z.max
+    }
+
+    #[must_use]
+    pub const fn centroid(&self) -> Point3 {
+        Point3::new(
+            f64::midpoint(self.x.min, self.x.max),
+            f64::midpoint(self.y.min, self.y.max),
+            f64::midpoint(self.z.min, self.z.max),
+        )
+    }
+
+    #[must_use]
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2.0 * dx.mul_add(dy, dy.mul_add(dz, dz * dx))
+    }
+
+    /// Index of the axis with the largest extent
+    #[must_use]
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test against the box, only counting hits inside `ray_t`
+    #[must_use]
+    pub fn hit(&self, r: &Ray, ray_t: &Interval) -> bool {
+        let orig = r.origin();
+        let dir = r.direction();
+        let mut t_min = ray_t.min;
+        let mut t_max = ray_t.max;
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / dir[axis];
+
+            let t0 = (ax.min - orig[axis]) * adinv;
+            let t1 = (ax.max - orig[axis]) * adinv;
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub const EMPTY: Self = Self {
+        x: Interval::EMPTY,
+        y: Interval::EMPTY,
+        z: Interval::EMPTY,
+    };
+    pub const UNIVERSE: Self = Self {
+        x: Interval::UNIVERSE,
+        y: Interval::UNIVERSE,
+        z: Interval::UNIVERSE,
+    };
+}
@@ -0,0 +1,345 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    color::{Color, luminance},
+    convert::{to_f64, to_usize},
+    image::{Image, WrapMode},
+    ray::Ray,
+    vec3::{Vec3, unit_vector},
+};
+
+/// Radiance seen by rays that leave the scene
+#[derive(Clone)]
+pub enum Background {
+    Solid(Color),
+    /// Blend by ray height, from `bottom` looking straight down to `top` looking straight up
+    Gradient { bottom: Color, top: Color },
+    Closure(Arc<dyn Fn(&Ray) -> Color + Send + Sync>),
+    Environment(Arc<EnvironmentMap>),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl Background {
+    /// Background computed by a user function of the escaping ray
+    pub fn from_fn(f: impl Fn(&Ray) -> Color + Send + Sync + 'static) -> Self {
+        Self::Closure(Arc::new(f))
+    }
+
+    #[must_use]
+    pub fn value(&self, r: &Ray) -> Color {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Gradient { bottom, top } => {
+                let unit_direction = unit_vector(r.direction());
+                let a = f64::midpoint(unit_direction.y(), 1.0);
+                (1.0 - a) * bottom + a * top
+            }
+            Self::Closure(f) => f(r),
+            Self::Environment(env) => env.value(r.direction()),
+        }
+    }
+}
+
+/// Equirectangular (latitude-longitude) image wrapped around the scene.
+/// The top row is straight up, the center column looks down -z
+pub struct EnvironmentMap {
+    image: Image,
+    distribution: Option<Distribution2d>, // Luminance distribution, `None` for a black map
+}
+
+impl EnvironmentMap {
+    #[must_use]
+    pub fn new(image: Image) -> Self {
+        let distribution = Distribution2d::new(&image);
+        Self {
+            image,
+            distribution,
+        }
+    }
+
+    #[must_use]
+    pub const fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// True if the map has any light to importance sample
+    #[must_use]
+    pub const fn is_importance_sampled(&self) -> bool {
+        self.distribution.is_some()
+    }
+
+    /// Bilinearly filtered radiance in `direction`
+    #[must_use]
+    pub fn value(&self, direction: &Vec3) -> Color {
+        let (u, v) = direction_to_uv(direction);
+        // Longitude wraps around, latitude clamps at the poles
+        self.image.bilinear(
+            u * to_f64(self.image.width()),
+            v * to_f64(self.image.height()),
+            WrapMode::Repeat,
+            WrapMode::Clamp,
+        )
+    }
+
+    /// Picks a direction with probability proportional to the luminance of the map,
+    /// from two uniform numbers in 0..1. Returns the direction and its density over solid angle
+    #[must_use]
+    pub fn sample(&self, u1: f64, u2: f64) -> Option<(Vec3, f64)> {
+        let (u, v, pdf_uv) = self.distribution.as_ref()?.sample(u1, u2);
+        if pdf_uv <= 0.0 {
+            return None;
+        }
+        // Uniform in cos(theta) across the row, so directions spread evenly over its solid angle
+        let rows = self.image.height();
+        let row = bucket(v, rows);
+        let across = v.mul_add(to_f64(rows), -to_f64(row));
+        let cos_theta = across.mul_add(row_cos(row + 1, rows) - row_cos(row, rows), row_cos(row, rows));
+        let sin_theta = cos_theta.mul_add(-cos_theta, 1.0).max(0.0).sqrt();
+        let phi = (u - 0.5) * 2.0 * PI;
+        let direction = Vec3::new(sin_theta * phi.sin(), cos_theta, -sin_theta * phi.cos());
+        Some((direction, solid_angle_pdf(pdf_uv, v, rows)))
+    }
+
+    /// Density over solid angle of `sample` returning `direction`
+    #[must_use]
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let Some(distribution) = &self.distribution else {
+            return 0.0;
+        };
+        let (u, v) = direction_to_uv(direction);
+        solid_angle_pdf(distribution.pdf(u, v), v, self.image.height())
+    }
+}
+
+/// Piecewise constant distribution over 0..1
+struct Distribution1d {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1d {
+    fn new(func: Vec<f64>) -> Self {
+        let n = to_f64(func.len());
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for f in &func {
+            cdf.push(cdf[cdf.len() - 1] + f / n);
+        }
+        let integral = cdf[cdf.len() - 1];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 { *c / integral } else { to_f64(i) / n };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    /// Maps a uniform `u` to a position in 0..1, returns it with its density and bucket
+    fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.func.len() - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let pdf = if self.integral > 0.0 {
+            self.func[offset] / self.integral
+        } else {
+            0.0
+        };
+        ((to_f64(offset) + du) / to_f64(self.func.len()), pdf, offset)
+    }
+}
+
+/// Distribution over the map pixels, weighted by the solid angle each row covers
+struct Distribution2d {
+    conditional: Vec<Distribution1d>, // Column distribution of each row
+    marginal: Distribution1d,         // Row distribution
+}
+
+impl Distribution2d {
+    fn new(image: &Image) -> Option<Self> {
+        let (width, height) = (image.width(), image.height());
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let conditional: Vec<Distribution1d> = image
+            .pixels()
+            .chunks(width)
+            .enumerate()
+            .map(|(y, row)| {
+                let band = row_cos(y, height) - row_cos(y + 1, height);
+                Distribution1d::new(row.iter().map(|c| luminance(c).max(0.0) * band).collect())
+            })
+            .collect();
+        let marginal = Distribution1d::new(conditional.iter().map(|d| d.integral).collect());
+        (marginal.integral > 0.0).then_some(Self {
+            conditional,
+            marginal,
+        })
+    }
+
+    /// Returns image coordinates in 0..1 and their density
+    fn sample(&self, u1: f64, u2: f64) -> (f64, f64, f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u2);
+        let (u, pdf_u, _) = self.conditional[row].sample(u1);
+        (u, v, pdf_u * pdf_v)
+    }
+
+    fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = bucket(v, self.conditional.len());
+        let column = bucket(u, self.conditional[row].func.len());
+        self.conditional[row].func[column] / self.marginal.integral
+    }
+}
+
+/// Maps a direction to image coordinates in 0..1, v grows downwards
+fn direction_to_uv(direction: &Vec3) -> (f64, f64) {
+    let d = unit_vector(direction);
+    let u = 0.5 + d.x().atan2(-d.z()) / (2.0 * PI);
+    let v = d.y().clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+
+/// Cosine of the polar angle at the top edge of `row`, the bottom edge of the last row is `rows`
+fn row_cos(row: usize, rows: usize) -> f64 {
+    (PI * to_f64(row) / to_f64(rows)).cos()
+}
+
+/// Density over solid angle in the texel at `v`, from the density over image coordinates.
+/// A texel covers `2 pi / width` of longitude times the band of `cos(theta)` its row spans
+fn solid_angle_pdf(pdf_uv: f64, v: f64, rows: usize) -> f64 {
+    let row = bucket(v, rows);
+    let band = row_cos(row, rows) - row_cos(row + 1, rows);
+    pdf_uv / (2.0 * PI * to_f64(rows) * band)
+}
+
+fn bucket(x: f64, n: usize) -> usize {
+    to_usize((x * to_f64(n)).max(0.0)).min(n - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Map of `width` by `height` texels with the value `f(x, y)` in every channel
+    fn map(width: usize, height: usize, f: impl Fn(usize, usize) -> f64) -> EnvironmentMap {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let value = f(x, y);
+                Color::new(value, value, value)
+            })
+            .collect();
+        EnvironmentMap::new(Image::from_pixels(width, height, pixels).expect("one pixel per texel"))
+    }
+
+    /// Uniform numbers at the centers of an `n` by `n` grid of strata
+    fn strata(n: usize) -> impl Iterator<Item = (f64, f64)> {
+        let center = move |i: usize| (to_f64(i) + 0.5) / to_f64(n);
+        (0..n).flat_map(move |i| (0..n).map(move |j| (center(i), center(j))))
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf() {
+        let env = map(16, 8, |x, y| to_f64((x * 7 + y * 3) % 11) + 0.5);
+        for (u1, u2) in strata(32) {
+            let (direction, pdf) = env.sample(u1, u2).expect("map has light");
+            assert!((direction.len() - 1.0).abs() < 1e-12);
+            let expected = env.pdf(&direction);
+            assert!((pdf - expected).abs() <= 1e-9 * expected, "sampled {pdf}, pdf gives {expected}");
+        }
+    }
+
+    #[test]
+    fn samples_single_bright_texel() {
+        let (width, height) = (16, 8);
+        let env = map(width, height, |x, y| if (x, y) == (5, 3) { 1000.0 } else { 0.001 });
+        let mut inside = 0;
+        let mut total = 0;
+        for (u1, u2) in strata(32) {
+            let (direction, _) = env.sample(u1, u2).expect("map has light");
+            let (u, v) = direction_to_uv(&direction);
+            inside += usize::from((bucket(u, width), bucket(v, height)) == (5, 3));
+            total += 1;
+        }
+        assert!(inside * 100 >= total * 99, "{inside} of {total} samples in the bright texel");
+    }
+
+    #[test]
+    fn constant_map_is_uniform() {
+        let env = map(64, 32, |_, _| 2.0);
+        let uniform = 1.0 / (4.0 * PI);
+        for (u1, u2) in strata(8) {
+            let (direction, pdf) = env.sample(u1, u2).expect("map has light");
+            assert!((pdf - uniform).abs() < 1e-12, "pdf {pdf} towards {:?}", direction.e);
+            assert!((env.pdf(&direction) - uniform).abs() < 1e-12);
+        }
+        for direction in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -0.9, 0.1)] {
+            assert!((env.pdf(&direction) - uniform).abs() < 1e-12, "pdf towards {:?}", direction.e);
+        }
+    }
+
+    #[test]
+    fn black_map_is_not_sampled() {
+        let env = map(4, 2, |_, _| 0.0);
+        assert!(!env.is_importance_sampled());
+        assert!(env.sample(0.5, 0.5).is_none());
+        assert!(env.pdf(&Vec3::new(0.0, 0.0, -1.0)).abs() < f64::EPSILON);
+    }
+
+    fn towards(x: f64, y: f64, z: f64) -> Ray {
+        Ray::new(&Vec3::new(1.0, 2.0, 3.0), &Vec3::new(x, y, z))
+    }
+
+    #[test]
+    fn solid_is_the_same_everywhere() {
+        let background = Background::Solid(Color::new(0.1, 0.2, 0.3));
+        for r in [towards(0.0, 1.0, 0.0), towards(0.0, -1.0, 0.0), towards(3.0, 0.5, -2.0)] {
+            assert_eq!(background.value(&r).e, [0.1, 0.2, 0.3]);
+        }
+    }
+
+    #[test]
+    fn gradient_blends_by_height() {
+        let background = Background::Gradient {
+            bottom: Color::new(1.0, 0.0, 0.0),
+            top: Color::new(0.0, 0.0, 1.0),
+        };
+        assert_eq!(background.value(&towards(0.0, 5.0, 0.0)).e, [0.0, 0.0, 1.0]);
+        assert_eq!(background.value(&towards(0.0, -0.5, 0.0)).e, [1.0, 0.0, 0.0]);
+        assert_eq!(background.value(&towards(2.0, 0.0, -1.0)).e, [0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn default_is_white_to_sky_blue() {
+        let background = Background::default();
+        assert_eq!(background.value(&towards(0.0, -1.0, 0.0)).e, [1.0, 1.0, 1.0]);
+        assert_eq!(background.value(&towards(0.0, 1.0, 0.0)).e, [0.5, 0.7, 1.0]);
+    }
+
+    #[test]
+    fn environment_looks_up_the_map() {
+        // Center column looks down -z, so the middle of a four column map is between columns 1 and 2
+        let env = map(4, 2, |x, _| to_f64(x));
+        let background = Background::Environment(Arc::new(env));
+        let forward = background.value(&towards(0.0, 0.0, -1.0));
+        assert!((forward.x() - 1.5).abs() < 1e-12, "{:?}", forward.e);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{
+    aabb::Aabb,
+    convert::{to_f64, to_usize},
+    hittable::{Hit, HitRecord, Hittable},
+    internal::Interval,
+    ray::Ray,
+    vec3::Point3,
+};
+
+const BINS: usize = 12; // Candidate split planes tested per axis
+const MAX_DEPTH: usize = 64; // Deeper subtrees are collapsed into leaves
+const MAX_LEAF_SIZE: usize = 4; // Largest leaf the build will accept over a worse split
+const TRAVERSAL_COST: f64 = 1.0; // SAH cost of visiting an interior node
+const INTERSECT_COST: f64 = 1.0; // SAH cost of testing a single object
+
+struct BvhNode {
+    bbox: Aabb,
+    start: usize, // First object of a leaf
+    count: usize, // Object count of a leaf, 0 for interior nodes
+    right: usize, // Right child of an interior node, the left child follows the node directly
+    axis: usize,  // Split axis of an interior node
+}
+
+struct Primitive<T> {
+    bbox: Aabb,
+    centroid: Point3,
+    object: T,
+}
+
+/// Bounding volume hierarchy over a set of objects, split with the surface area heuristic
+pub struct Bvh<T: Hit = Hittable> {
+    nodes: Vec<BvhNode>,
+    objects: Vec<T>,
+}
+
+impl<T: Hit> Bvh<T> {
+    /// Builds the hierarchy, objects with an empty bounding box (`Hittable::Empty`) are dropped
+    #[must_use]
+    pub fn new(objects: Vec<T>) -> Self {
+        let mut prims: Vec<Primitive<T>> = objects
+            .into_iter()
+            .filter_map(|object| {
+                let bbox = object.bounding_box();
+                (!bbox.is_empty()).then(|| Primitive {
+                    bbox,
+                    centroid: bbox.centroid(),
+                    object,
+                })
+            })
+            .collect();
+        let mut nodes = Vec::with_capacity(2 * prims.len());
+        if !prims.is_empty() {
+            build(&mut nodes, &mut prims, 0, 0);
+        }
+        let objects = prims.into_iter().map(|p| p.object).collect();
+        Self { nodes, objects }
+    }
+}
+
+impl<T: Hit> Hit for Bvh<T> {
+    fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |n| n.bbox)
+    }
+
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let mut stack = [0_usize; MAX_DEPTH + 1];
+        let mut top = 1;
+        let mut hit_anything = false;
+        let mut closest_so_far = ray_t.max;
+        while top > 0 {
+            top -= 1;
+            let index = stack[top];
+            let node = &self.nodes[index];
+            let node_t = Interval::new(ray_t.min, &closest_so_far);
+            if !node.bbox.hit(r, &node_t) {
+                continue;
+            }
+            if node.count > 0 {
+                for object in &self.objects[node.start..node.start + node.count] {
+                    // Custom primitives may write to the record on a miss, so hit into a scratch one
+                    let mut temp_rec = HitRecord::default();
+                    if object.hit(r, &Interval::new(ray_t.min, &closest_so_far), &mut temp_rec) {
+                        hit_anything = true;
+                        closest_so_far = temp_rec.t;
+                        *rec = temp_rec;
+                    }
+                }
+                continue;
+            }
+            // Visit the child nearer to the ray origin first so the far one can be culled
+            let (near, far) = if r.direction()[node.axis] < 0.0 {
+                (node.right, index + 1)
+            } else {
+                (index + 1, node.right)
+            };
+            stack[top] = far;
+            stack[top + 1] = near;
+            top += 2;
+        }
+        hit_anything
+    }
+}
+
+fn build<T>(nodes: &mut Vec<BvhNode>, prims: &mut [Primitive<T>], start: usize, depth: usize) -> usize {
+    let index = nodes.len();
+    let bbox = prims
+        .iter()
+        .fold(Aabb::EMPTY, |acc, p| Aabb::surrounding(&acc, &p.bbox));
+    nodes.push(BvhNode {
+        bbox,
+        start,
+        count: prims.len(),
+        right: 0,
+        axis: 0,
+    });
+    if prims.len() == 1 || depth >= MAX_DEPTH {
+        return index;
+    }
+
+    let Some((axis, mid)) = sah_split(prims, &bbox) else {
+        return index;
+    };
+    nodes[index].count = 0;
+    nodes[index].axis = axis;
+    let (left, right) = prims.split_at_mut(mid);
+    build(nodes, left, start, depth + 1);
+    nodes[index].right = build(nodes, right, start + mid, depth + 1);
+    index
+}
+
+/// Finds the cheapest binned split and partitions `prims` around it.
+/// Returns the split axis and partition point, or `None` if a leaf is cheaper
+fn sah_split<T>(prims: &mut [Primitive<T>], bbox: &Aabb) -> Option<(usize, usize)> {
+    let centroid_bounds = prims.iter().fold(Aabb::EMPTY, |acc, p| {
+        Aabb::surrounding(&acc, &Aabb::from_points(&p.centroid, &p.centroid))
+    });
+
+    let mut best: Option<(usize, usize, f64)> = None; // axis, bin, cost
+    for axis in 0..3 {
+        let bounds = centroid_bounds.axis_interval(axis);
+        if bounds.size() <= 0.0 {
+            continue;
+        }
+        let mut bin_boxes = [Aabb::EMPTY; BINS];
+        let mut bin_counts = [0_usize; BINS];
+        for p in prims.iter() {
+            let b = bin_index(p.centroid[axis], bounds);
+            bin_boxes[b] = Aabb::surrounding(&bin_boxes[b], &p.bbox);
+            bin_counts[b] += 1;
+        }
+
+        // Sweep from the right to get the cost contribution of every right hand side
+        let mut right_area = [0.0; BINS];
+        let mut right_count = [0_usize; BINS];
+        let mut acc_box = Aabb::EMPTY;
+        let mut acc_count = 0;
+        for b in (1..BINS).rev() {
+            acc_box = Aabb::surrounding(&acc_box, &bin_boxes[b]);
+            acc_count += bin_counts[b];
+            right_area[b] = acc_box.surface_area();
+            right_count[b] = acc_count;
+        }
+
+        let mut acc_box = Aabb::EMPTY;
+        let mut acc_count = 0;
+        for b in 1..BINS {
+            acc_box = Aabb::surrounding(&acc_box, &bin_boxes[b - 1]);
+            acc_count += bin_counts[b - 1];
+            if acc_count == 0 || right_count[b] == 0 {
+                continue;
+            }
+            let cost = acc_box
+                .surface_area()
+                .mul_add(to_f64(acc_count), right_area[b] * to_f64(right_count[b]));
+            if best.is_none_or(|(_, _, c)| cost < c) {
+                best = Some((axis, b, cost));
+            }
+        }
+    }
+
+    let (axis, split, cost) = best?;
+    let parent_area = bbox.surface_area();
+    let split_cost = if parent_area > 0.0 {
+        TRAVERSAL_COST + INTERSECT_COST * cost / parent_area
+    } else {
+        TRAVERSAL_COST
+    };
+    let leaf_cost = INTERSECT_COST * to_f64(prims.len());
+    if split_cost >= leaf_cost && prims.len() <= MAX_LEAF_SIZE {
+        return None;
+    }
+
+    let bounds = centroid_bounds.axis_interval(axis);
+    let mut mid = 0;
+    for i in 0..prims.len() {
+        if bin_index(prims[i].centroid[axis], bounds) < split {
+            prims.swap(i, mid);
+            mid += 1;
+        }
+    }
+    Some((axis, mid))
+}
+
+fn bin_index(c: f64, bounds: &Interval) -> usize {
+    let b = to_usize((c - bounds.min) / bounds.size() * to_f64(BINS));
+    b.min(BINS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        RenderRng,
+        color::Color,
+        hittable_list::HittableList,
+        material::{Lambertain, Mat},
+        sphere::Sphere,
+        texture::Texture,
+        vec3::Vec3,
+    };
+
+    /// Never hit, but scribbles over the record while missing
+    struct Scribbler;
+
+    impl Hit for Scribbler {
+        fn hit<'a>(&'a self, _r: &Ray, _ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+            rec.t = 0.5;
+            false
+        }
+
+        fn bounding_box(&self) -> Aabb {
+            Aabb::from_points(&Point3::new(-1.0, -1.0, -6.0), &Point3::new(1.0, 1.0, -4.0))
+        }
+    }
+
+    #[test]
+    fn miss_in_leaf_keeps_closest_hit() {
+        let mat = Mat::Lambertain(Lambertain {
+            albedo: Texture::Solid(Color::new(0.5, 0.5, 0.5)),
+        });
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, -5.0), 1.0, mat);
+        let bvh = Bvh::new(vec![Hittable::custom(Scribbler), sphere.into(), Hittable::custom(Scribbler)]);
+        let r = Ray::new(&Point3::default(), &Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(bvh.hit(&r, &Interval::new(0.001, &f64::INFINITY), &mut rec));
+        assert!((rec.t - 4.0).abs() < 1e-12, "hit at t = {}", rec.t);
+    }
+
+    #[test]
+    fn hits_match_linear_scan() {
+        let mut rng = RenderRng::seed_from_u64(9);
+        let mut point = |scale: f64| {
+            Point3::new(rng.random_range(-scale..scale), rng.random_range(-scale..scale), rng.random_range(-scale..scale))
+        };
+        let mat = Mat::Lambertain(Lambertain {
+            albedo: Texture::Solid(Color::new(0.5, 0.5, 0.5)),
+        });
+        let list: HittableList = (0..200).map(|k| Sphere::new(point(10.0), f64::from(k % 5).mul_add(0.2, 0.1), mat.clone())).collect();
+        let bvh = Bvh::new(list.objects().to_vec());
+        let ray_t = Interval::new(0.001, &f64::INFINITY);
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let origin = point(15.0);
+            let r = Ray::new(&origin, &(point(5.0) - origin));
+            let (mut expected, mut found) = (HitRecord::default(), HitRecord::default());
+            let hit = list.hit(&r, &ray_t, &mut expected);
+            assert_eq!(bvh.hit(&r, &ray_t, &mut found), hit);
+            if hit {
+                assert!((found.t - expected.t).abs() < 1e-12, "{} instead of {}", found.t, expected.t);
+                hits += 1;
+            }
+        }
+        assert!(hits > 100, "only {hits} rays hit a sphere");
+    }
+}
@@ -1,28 +1,44 @@
 use crate::{
-    color::{
-        Color,
-        write_color,
-    },
-    hittable::HitRecord,
+    background::{Background, EnvironmentMap},
+    bvh::Bvh,
+    cancel::CancelToken,
+    color::Color,
+    convert::{to_f64, to_i32, truncate_to_i32},
+    film::{Film, FilmPixel, sample_heatmap},
+    filter::Filter,
+    hdr::HdrError,
+    hittable::{Hit, HitRecord},
     hittable_list::HittableList,
+    image::Image,
     internal::Interval,
-    rand_f64,
+    material::Scatter,
+    obj::ObjError,
+    output::{ImageFormat, write_image},
+    progress::{Progress, ProgressSink, TerminalProgress},
     ray::Ray,
+    sampler::{Sampler, SamplerKind},
+    tile::{CloseOnDrop, Tile, TileJob, TileOrder, TileQueue, tiles},
+    tonemap::{DisplayTransform, ToneMap},
     vec3::{
         Point3,
         Vec3,
         cross,
-        random_in_unit_disk,
+        sample_unit_disk,
         unit_vector,
     },
 };
 use std::{
-    fs::File,
-    io::Write,
-    sync::{Arc, Mutex},
+    error::Error,
+    fmt,
+    io,
+    panic,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock, mpsc},
     thread,
+    time::{Duration, Instant},
 };
 
+#[derive(Clone)]
 pub struct Camera {
     // Public
     pub aspect_ratio: f64,      // Ratio of image width over image_height
@@ -35,9 +51,22 @@ pub struct Camera {
     pub vup: Vec3,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    pub background: Background,                   // Radiance of rays that miss every object
+    pub image_format: Option<ImageFormat>,        // Output file format, picked from the file extension when None
+    pub seed: u64,                                // Renders with the same seed and settings are identical
+    pub tile_size: usize,                         // Width and height of the square tiles the image is rendered in
+    pub tile_order: TileOrder,                    // Order tiles are rendered in
+    pub progress: Arc<dyn ProgressSink>,          // Told about every completed tile
+    pub time_budget: Option<Duration>,            // Stop at this render time, adding passes until then unless progressive
+    pub cancel: CancelToken,                      // Stops the render early, keeping the samples taken so far
+    pub progressive: bool,                        // Render one sample per pixel per pass, up to samples_per_pixel
+    pub snapshot_every: Option<SnapshotInterval>, // How often to pass on the image so far between passes
+    pub adaptive: Option<AdaptiveSampling>,       // Keep sampling only the pixels that are still noisy, see `AdaptiveSampling`
+    pub sampler: SamplerKind,                     // Source of the pixel, lens and bounce sample values
+    pub filter: Filter,                           // Weighting of samples splatted onto the pixels around them
+    pub display: DisplayTransform,                // Exposure and tone mapping for 8 bit output formats
     // Private
     pub(super) image_height: i32,        // Rendered image height
-    pub(super) pixel_samples_scale: f64, // Color scale factor for a sum of pixel samples
     pub(super) pixel00_loc: Point3,      // Location of pixel at 0, 0
     pub(super) pixel_delta_u: Vec3,      // Offset to pixel to the right
     pub(super) pixel_delta_v: Vec3,      // Offset to pixel below
@@ -61,11 +90,24 @@ impl Default for Camera {
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            background: Background::default(),
+            image_format: None,
+            seed: 0,
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            progress: Arc::new(TerminalProgress),
+            time_budget: None,
+            cancel: CancelToken::default(),
+            progressive: false,
+            snapshot_every: None,
+            adaptive: None,
+            sampler: SamplerKind::default(),
+            filter: Filter::default(),
+            display: DisplayTransform::default(),
             image_height: i32::default(),
             pixel00_loc: Vec3::default(),
             pixel_delta_v: Vec3::default(),
             pixel_delta_u: Vec3::default(),
-            pixel_samples_scale: f64::default(),
             u: Vec3::default(),
             v: Vec3::default(),
             w: Vec3::default(),
@@ -75,15 +117,153 @@ impl Default for Camera {
     }
 }
 
+/// How often a render hands out snapshots of the image so far, checked after every pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotInterval {
+    Passes(usize),  // Every this many passes
+    Time(Duration), // After the first pass ending at least this long after the last snapshot
+}
+
+/// Settings for spending samples on noisy pixels rather than evenly. Every pass takes the usual
+/// samples for each pixel that is not converged yet, until none are left
+///
+/// `max_samples` takes the place of `samples_per_pixel` as the most a pixel gets, which is then
+/// only the samples per pass. `progressive` cuts the passes down to one sample each, and a
+/// `time_budget` still ends the render early
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    pub threshold: f64,   // Relative standard error of a pixel's mean luminance at which it is converged
+    pub min_samples: u32, // Samples every pixel takes before its error is trusted
+    pub max_samples: u32, // Samples no pixel goes beyond, converged or not
+    pub heatmap: bool,    // Have `render` also write the sample counts as an image, see `film::sample_heatmap`
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            threshold: 0.01,
+            min_samples: 16,
+            max_samples: 1024,
+            heatmap: false,
+        }
+    }
+}
+
+/// Reason a camera cannot be rendered from, see `Camera::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CameraError {
+    NotFinite { field: &'static str },                          // NaN or infinite
+    OutOfRange { field: &'static str, expected: &'static str }, // Finite but unusable
+    LookFromIsLookAt,                                           // No view direction
+    VupParallelToView,                                          // No way to tell up from the view direction
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFinite { field } => write!(f, "{field} must be finite"),
+            Self::OutOfRange { field, expected } => write!(f, "{field} must be {expected}"),
+            Self::LookFromIsLookAt => write!(f, "look_from and look_at must differ"),
+            Self::VupParallelToView => write!(f, "vup must not be parallel to the view direction"),
+        }
+    }
+}
+
+impl Error for CameraError {}
+
 impl Camera {
+    /// Checks that every setting is usable, `render` and `render_to_buffer` call this first
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error naming the first setting that is non-finite, out of
+    /// range, or leaves the view direction or orientation undefined
+    pub fn validate(&self) -> Result<(), CameraError> {
+        let out_of_range = |field, expected| Err(CameraError::OutOfRange { field, expected });
+        let floats = [
+            ("aspect_ratio", self.aspect_ratio),
+            ("vfov", self.vfov),
+            ("defocus_angle", self.defocus_angle),
+            ("focus_dist", self.focus_dist),
+        ];
+        let vectors = [("look_from", &self.look_from), ("look_at", &self.look_at), ("vup", &self.vup)];
+        if let Some((field, _)) = floats.iter().find(|(_, x)| !x.is_finite()) {
+            return Err(CameraError::NotFinite { field });
+        }
+        if let Some((field, _)) = vectors.iter().find(|(_, v)| !v.e.iter().all(|x| x.is_finite())) {
+            return Err(CameraError::NotFinite { field });
+        }
+
+        if self.image_width < 1 {
+            return out_of_range("image_width", "at least 1");
+        }
+        if self.samples_per_pixel < 1 {
+            return out_of_range("samples_per_pixel", "at least 1");
+        }
+        if self.max_depth < 1 {
+            return out_of_range("max_depth", "at least 1");
+        }
+        if self.tile_size < 1 {
+            return out_of_range("tile_size", "at least 1");
+        }
+        if !self.filter.radius.is_finite() {
+            return Err(CameraError::NotFinite { field: "filter.radius" });
+        }
+        if self.filter.radius < 0.5 {
+            return out_of_range("filter.radius", "at least half a pixel");
+        }
+        if !self.display.exposure.is_finite() {
+            return Err(CameraError::NotFinite { field: "display.exposure" });
+        }
+        if let ToneMap::ExtendedReinhard { white } = self.display.tone_map
+            && !(white.is_finite() && white > 0.0)
+        {
+            return out_of_range("display.tone_map", "an extended Reinhard white point that is finite and positive");
+        }
+        if self.snapshot_every == Some(SnapshotInterval::Passes(0)) {
+            return out_of_range("snapshot_every", "at least 1 pass");
+        }
+        if let Some(adaptive) = self.adaptive {
+            if !adaptive.threshold.is_finite() {
+                return Err(CameraError::NotFinite { field: "adaptive.threshold" });
+            }
+            if adaptive.threshold <= 0.0 {
+                return out_of_range("adaptive.threshold", "positive");
+            }
+            if adaptive.max_samples < adaptive.min_samples.max(1) {
+                return out_of_range("adaptive.max_samples", "at least 1 and at least adaptive.min_samples");
+            }
+        }
+        if self.aspect_ratio <= 0.0 {
+            return out_of_range("aspect_ratio", "positive");
+        }
+        if f64::from(self.image_width) / self.aspect_ratio > f64::from(i32::MAX) {
+            return out_of_range("aspect_ratio", "large enough for the image height to fit in an i32");
+        }
+        if self.vfov <= 0.0 || self.vfov >= 180.0 {
+            return out_of_range("vfov", "between 0 and 180 degrees");
+        }
+        if self.defocus_angle < 0.0 || self.defocus_angle >= 180.0 {
+            return out_of_range("defocus_angle", "at least 0 and below 180 degrees");
+        }
+        if self.focus_dist <= 0.0 {
+            return out_of_range("focus_dist", "positive");
+        }
+
+        let view = self.look_from - self.look_at;
+        if view.near_zero() {
+            return Err(CameraError::LookFromIsLookAt);
+        }
+        if self.vup.near_zero() || cross(&unit_vector(&self.vup), &unit_vector(&view)).near_zero() {
+            return Err(CameraError::VupParallelToView);
+        }
+        Ok(())
+    }
+
+    /// Derives the private fields, the settings must have passed `validate`
     fn init(&mut self) {
-        self.image_height = unsafe { (f64::from(self.image_width) / self.aspect_ratio).to_int_unchecked() };
-        self.image_height = match self.image_height {
-            x if x < 1 => 1,
-            _ => self.image_height,
-        };
+        self.image_height = truncate_to_i32(f64::from(self.image_width) / self.aspect_ratio).max(1);
 
-        self.pixel_samples_scale = 1.0 / f64::from(self.samples_per_pixel);
         // Determine viewport dimensions
         let theta = self.vfov.to_radians();
         let h = (theta / 2.0).tan();
@@ -106,70 +286,347 @@ impl Camera {
             self.look_from - (self.focus_dist * self.w) - viewport_u / 2.0 - viewport_v / 2.0;
         self.pixel00_loc = viewport_upper_left + 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
 
-        let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0).to_degrees().tan();
+        let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0).to_radians().tan();
         self.defocus_disk_u = self.u * defocus_radius;
         self.defocus_disk_v = self.v * defocus_radius;
     }
+
+    /// Samples taken for each pixel in one pass
+    const fn samples_per_pass(&self) -> i32 {
+        if self.progressive { 1 } else { self.samples_per_pixel }
+    }
+
+    /// Number of passes in a render, `None` when they go on until the time budget runs out or
+    /// adaptive sampling has no pixels left to sample
+    fn passes(&self) -> Option<usize> {
+        if self.adaptive.is_some() {
+            None
+        } else if self.progressive {
+            usize::try_from(self.samples_per_pixel).ok()
+        } else if self.time_budget.is_some() {
+            None
+        } else {
+            Some(1)
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct RenderError;
+pub enum RenderError {
+    Io { path: PathBuf, source: io::Error }, // Reading or writing a file failed
+    UnknownFormat { path: PathBuf },         // No image format given and none matches the file extension
+    InvalidCamera(CameraError),              // The camera settings cannot produce an image
+    Scene(Box<dyn Error + Send + Sync>),     // Loading or building the scene failed
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::UnknownFormat { path } => write!(f, "{}: unknown image format", path.display()),
+            Self::InvalidCamera(error) => write!(f, "invalid camera: {error}"),
+            Self::Scene(source) => write!(f, "scene error: {source}"),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Scene(source) => Some(source.as_ref()),
+            Self::InvalidCamera(source) => Some(source),
+            Self::UnknownFormat { .. } => None,
+        }
+    }
+}
+
+impl From<ObjError> for RenderError {
+    fn from(e: ObjError) -> Self {
+        Self::Scene(Box::new(e))
+    }
+}
+
+impl From<HdrError> for RenderError {
+    fn from(e: HdrError) -> Self {
+        Self::Scene(Box::new(e))
+    }
+}
 
-/// .
+/// Renders `world` and writes the image to `file_name` in `cam.image_format`, or the format
+/// named by the file extension
+///
+/// Snapshots requested by `cam.snapshot_every` overwrite the file as the render goes. An
+/// adaptive sampling heatmap goes next to it, with `_samples` appended to the file stem
 ///
 /// # Errors
 ///
-/// This function will return an error if 
-/// It fials to write to file
-pub fn render<const L: usize>(
-    cam: &mut Camera,
+/// This function will return an error if the output format is unknown, rendering fails or
+/// the file cannot be written
+pub fn render(
+    cam: &Camera,
     file_name: &str,
-    world: &HittableList<L>,
+    world: &HittableList,
 ) -> Result<(), RenderError>
 {
+    let format = cam
+        .image_format
+        .or_else(|| ImageFormat::from_path(file_name))
+        .ok_or_else(|| RenderError::UnknownFormat {
+            path: file_name.into(),
+        })?;
+    let write = |image: &Image| {
+        write_image(file_name, image, format, &cam.display).map_err(|source| RenderError::Io {
+            path: file_name.into(),
+            source,
+        })
+    };
+    let image = render_with_snapshots(cam, world, write)?;
+    write(&image)?;
+    if cam.adaptive.is_some_and(|adaptive| adaptive.heatmap)
+        && let Some(heatmap) = sample_heatmap(&image)
+    {
+        let path = heatmap_path(Path::new(file_name));
+        write_image(&path, &heatmap, format, &DisplayTransform::default()).map_err(|source| RenderError::Io { path, source })?;
+    }
+    Ok(())
+}
+
+/// `file_name` with `_samples` appended to its stem
+fn heatmap_path(file_name: &Path) -> PathBuf {
+    let mut name = file_name.file_stem().unwrap_or_default().to_os_string();
+    name.push("_samples");
+    if let Some(extension) = file_name.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    file_name.with_file_name(name)
+}
+
+/// Renders `world` into an image of linear, unclamped radiance values
+///
+/// A bounding volume hierarchy is built over the objects of `world` first. The number of samples
+/// each pixel received is stored in the `film::SAMPLES_AOV` AOV, which is uneven when the render
+/// was cancelled or ran out of time
+///
+/// # Errors
+///
+/// This function will return an error if the camera settings are invalid, see `Camera::validate`
+pub fn render_to_buffer(cam: &Camera, world: &HittableList) -> Result<Image, RenderError> {
+    render_with_snapshots(cam, world, |_| Ok(()))
+}
+
+/// Renders `world` like `render_to_buffer`, calling `snapshot` with the image so far as often
+/// as `cam.snapshot_every` asks. The final image is returned rather than passed to `snapshot`
+///
+/// # Errors
+///
+/// This function will return an error if the camera settings are invalid, or the first error
+/// returned by `snapshot`, which stops the render
+pub fn render_with_snapshots(
+    cam: &Camera,
+    world: &HittableList,
+    mut snapshot: impl FnMut(&Image) -> Result<(), RenderError>,
+) -> Result<Image, RenderError> {
+    cam.validate().map_err(RenderError::InvalidCamera)?;
+    let mut cam = cam.clone();
     cam.init();
-    let mut image_file = std::io::BufWriter::new(File::create(file_name).map_err(|_| RenderError)?);
-    let _ = write!(
-        image_file,
-        "P3\n {} {}\n255\n",
-        cam.image_width, cam.image_height
-    ).map_err(|_| RenderError);
-    let cam = Arc::new(cam);
-    let world = Arc::new(world);
-    for j in 0..cam.image_height {
-        print!("\rScanlines remaining: {} ", (cam.image_height - j));
-        let mut res = vec![String::new(); cam.image_width.try_into().map_err(|_| RenderError)?];
-        let jobs =    Arc::new(Mutex::new((0..cam.image_width).zip(res.iter_mut())));
-        let count = thread::available_parallelism().map_err(|_| RenderError)?.get();
-        thread::scope(|scope| {
-            for _ in 0..count.max(1) {
-            let jobs = jobs.clone();
-            let world = world.clone();
-            let cam = cam.clone();
+    let size_error = || {
+        RenderError::InvalidCamera(CameraError::OutOfRange {
+            field: "image_width",
+            expected: "at least 1",
+        })
+    };
+    let width: usize = cam.image_width.try_into().map_err(|_| size_error())?;
+    let height: usize = cam.image_height.try_into().map_err(|_| size_error())?;
+    let mut film = Film::new(width, height);
+    let world: Bvh = Bvh::new(world.objects().to_vec());
+    let tiles = tiles(width, height, cam.tile_size, cam.tile_order);
+    let workers = thread::available_parallelism().map_or(1, std::num::NonZero::get).min(tiles.len());
+    let queue = TileQueue::new(workers);
+    let (sender, receiver) = mpsc::channel();
+    let start = Instant::now();
+    let deadline = cam.time_budget.map(|budget| start + budget);
+    let stop = || cam.cancel.is_cancelled() || deadline.is_some_and(|d| Instant::now() >= d);
+    let passes = cam.passes();
+    let plan = RwLock::new(Vec::new());
+    thread::scope(|scope| -> Result<(), RenderError> {
+        let handles: Vec<_> = (0..workers)
+            .map(|worker| {
+                let sender = sender.clone();
+                let (cam, world, queue, plan, stop) = (&cam, &world, &queue, &plan, &stop);
                 scope.spawn(move || {
-                    let next = || jobs.lock().ok()?.next();
-                    while let Some((i, o)) = next() {
-                        let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                        for _ in 0..cam.samples_per_pixel {
-                            let r = get_ray(&cam, i, j);
-                            pixel_color += ray_color(&r, cam.max_depth, &world);
-                        }
-                        *o = write_color(&(pixel_color * cam.pixel_samples_scale));
+                    work_tiles(queue, worker, &sender, |job| {
+                        let plan = plan.read().unwrap_or_else(PoisonError::into_inner);
+                        render_tile(cam, world, job, &plan, (width, height), stop)
+                    });
+                })
+            })
+            .collect();
+        drop(sender);
+        let _close = CloseOnDrop(&queue);
+        let mut tiles_done = 0;
+        let mut last_snapshot = start;
+        let mut result = Ok(());
+        for pass in 0.. {
+            let next_plan = plan_pass(&cam, &film);
+            let active = active_tiles(&tiles, &next_plan, width);
+            *plan.write().unwrap_or_else(PoisonError::into_inner) = next_plan;
+            if active.is_empty() {
+                break;
+            }
+            queue.push_pass(pass, &active);
+            let tiles_total = passes.map_or(tiles_done + active.len(), |passes| passes * tiles.len());
+            let mut rendered = Vec::with_capacity(active.len());
+            for (job, region, pixels) in receiver.iter().take(active.len()) {
+                rendered.push((region, pixels));
+                tiles_done += 1;
+                let mut progress = Progress::new(job.tile, pass, tiles_done, tiles_total, start.elapsed());
+                if let Some(deadline) = deadline {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    progress.eta = Some(match (passes, progress.eta) {
+                        (Some(_), Some(eta)) => eta.min(left),
+                        _ => left,
+                    });
+                }
+                cam.progress.tile_done(&progress);
+            }
+            // Results only stop short when a worker panicked, which joining it passes on below
+            if rendered.len() < active.len() {
+                break;
+            }
+            // Tiles overlap where samples are splatted across their edges, adding them in a fixed
+            // order keeps the sums independent of the order the tiles finished in
+            rendered.sort_by_key(|(region, _)| (region.y0, region.x0));
+            for (region, pixels) in &rendered {
+                film.add_tile(region, pixels);
+            }
+            if stop() || passes.is_some_and(|passes| pass + 1 >= passes) {
+                break;
+            }
+            let due = match cam.snapshot_every {
+                Some(SnapshotInterval::Passes(n)) => (pass + 1) % n == 0,
+                Some(SnapshotInterval::Time(interval)) => last_snapshot.elapsed() >= interval,
+                None => false,
+            };
+            if due {
+                result = snapshot(&film.to_image());
+                if result.is_err() {
+                    break;
+                }
+                last_snapshot = Instant::now();
+            }
+        }
+        queue.close();
+        for handle in handles {
+            if let Err(panic) = handle.join() {
+                panic::resume_unwind(panic);
+            }
+        }
+        result
+    })?;
+    cam.progress.finished(start.elapsed());
+    Ok(film.to_image())
+}
+
+/// Renders jobs taken from `queue` for `worker` and sends them with their results until the
+/// queue is closed
+fn work_tiles(
+    queue: &TileQueue,
+    worker: usize,
+    sender: &mpsc::Sender<(TileJob, Tile, Vec<FilmPixel>)>,
+    render: impl Fn(&TileJob) -> (Tile, Vec<FilmPixel>),
+) {
+    // A worker that panics releases the others, whose senders then go away and end the main
+    // thread's wait for results
+    let _close = CloseOnDrop(queue);
+    while let Some(job) = queue.next(worker) {
+        let (region, pixels) = render(&job);
+        // The receiver only goes away if the main thread panicked
+        if sender.send((job, region, pixels)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Samples for every pixel of the film in the next pass, zero for pixels that are done
+fn plan_pass(cam: &Camera, film: &Film) -> Vec<u32> {
+    let samples = cam.samples_per_pass().unsigned_abs();
+    let Some(adaptive) = cam.adaptive else {
+        return vec![samples; film.pixels().len()];
+    };
+    film.pixels()
+        .iter()
+        .map(|pixel| {
+            let converged = pixel.samples >= adaptive.min_samples && pixel.relative_error() <= adaptive.threshold;
+            if converged { 0 } else { samples.min(adaptive.max_samples.saturating_sub(pixel.samples)) }
+        })
+        .collect()
+}
+
+/// Tiles with at least one pixel to sample in `plan`, for a film `width` pixels wide
+fn active_tiles(tiles: &[Tile], plan: &[u32], width: usize) -> Vec<Tile> {
+    tiles.iter()
+        .filter(|tile| (tile.y0..tile.y1).any(|j| plan[j * width + tile.x0..j * width + tile.x1].iter().any(|&n| n > 0)))
+        .copied()
+        .collect()
+}
+
+/// Samples of every pixel in the tile of `job` for one pass, taking as many as `plan` gives
+/// each pixel of the `width` by `height` film. Returns the samples splatted onto the tile and
+/// the border around it the filter reaches, row by row, along with that region. Pixels reached
+/// after `stop` returns true get no samples
+fn render_tile(
+    cam: &Camera,
+    world: &Bvh,
+    job: &TileJob,
+    plan: &[u32],
+    (width, height): (usize, usize),
+    stop: &impl Fn() -> bool,
+) -> (Tile, Vec<FilmPixel>) {
+    let tile = &job.tile;
+    let border = cam.filter.border();
+    let region = tile.expanded(border, width, height);
+    let mut pixels = vec![FilmPixel::default(); region.pixel_count()];
+    // Pixels still being sampled have taken every sample of the earlier passes
+    let first_sample = to_i32(job.pass) * cam.samples_per_pass();
+    for (x, y) in (tile.y0..tile.y1).flat_map(|y| (tile.x0..tile.x1).map(move |x| (x, y))) {
+        if stop() {
+            break;
+        }
+        let sample_count = plan[y * width + x].cast_signed();
+        // Fits, as `Camera::validate` bounds the image size
+        let (i, j) = (to_i32(x), to_i32(y));
+        let reach_x = x.saturating_sub(border).max(region.x0)..(x + border + 1).min(region.x1);
+        let reach_y = y.saturating_sub(border).max(region.y0)..(y + border + 1).min(region.y1);
+        for sample in first_sample..first_sample + sample_count {
+            let mut sampler = Sampler::new(
+                cam.sampler,
+                cam.seed,
+                [i.cast_unsigned(), j.cast_unsigned()],
+                sample.cast_unsigned(),
+                cam.samples_per_pixel.cast_unsigned(),
+            );
+            let offset = sample_square(&mut sampler);
+            let r = get_ray(cam, i, j, &offset, &mut sampler);
+            let color = ray_color(&r, cam.max_depth, world, &cam.background, &mut sampler);
+            pixels[(y - region.y0) * region.width() + x - region.x0].add_sample(color);
+            for ny in reach_y.clone() {
+                for nx in reach_x.clone() {
+                    let dx = to_f64(nx) - to_f64(x) - offset.x();
+                    let dy = to_f64(ny) - to_f64(y) - offset.y();
+                    let weight = cam.filter.weight(dx, dy);
+                    if weight != 0.0 {
+                        pixels[(ny - region.y0) * region.width() + nx - region.x0].splat(color, weight);
                     }
-                });
+                }
             }
-        });
-        for ele in res {
-            writeln!(image_file, "{}", &ele).map_err(|_| RenderError)?;
         }
-        std::io::stdout().flush().map_err(|_| RenderError)?;
     }
-    image_file.flush().map_err(|_| RenderError)?;
-    print!("{:<23}", "\rDone");
-    Ok(())
+    (region, pixels)
 }
 
-fn ray_color<const L: usize>(r: &Ray, depth: i32, world: &HittableList<L>) -> Color {
+fn ray_color(r: &Ray, depth: i32, world: &Bvh, background: &Background, sampler: &mut Sampler) -> Color {
     if depth <= 0 {
         return Color::new(0.0, 0.0, 0.0);
     }
@@ -177,35 +634,610 @@ fn ray_color<const L: usize>(r: &Ray, depth: i32, world: &HittableList<L>) -> Co
     if world.hit(r, &Interval::new(0.001, &f64::INFINITY), &mut rec) {
         let mut scattered = Ray::new(&Vec3::default(), &Vec3::default());
         let mut attenuation = Color::default();
-        if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-            return attenuation * ray_color(&scattered, depth - 1, world);
+        let emission = rec.mat.emitted(r, &rec);
+        if !rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered, sampler) {
+            return emission;
         }
-        return Color::new(0.0, 0.0, 0.0);
+        if let Background::Environment(env) = background
+            && env.is_importance_sampled()
+            && rec.mat.scattering_pdf(r, &rec, &scattered) > 0.0
+        {
+            let (scattered, weight) = environment_sampled_ray(r, &rec, scattered, env, sampler);
+            if weight <= 0.0 {
+                return emission;
+            }
+            return emission + weight * attenuation * ray_color(&scattered, depth - 1, world, background, sampler);
+        }
+        return emission + attenuation * ray_color(&scattered, depth - 1, world, background, sampler);
+    }
+    background.value(r)
+}
+
+/// Diffuse bounce sampled half the time from the material and half the time from the
+/// environment map, so bright parts of the map are found without relying on chance.
+/// Returns the ray along with the weight correcting for the mix of both strategies
+fn environment_sampled_ray(
+    r: &Ray,
+    rec: &HitRecord,
+    mut scattered: Ray,
+    env: &EnvironmentMap,
+    sampler: &mut Sampler,
+) -> (Ray, f64) {
+    // Both values are drawn either way, so later bounces see the same dimensions
+    let choice = sampler.next_1d();
+    let (u, v) = sampler.next_2d();
+    if choice < 0.5
+        && let Some((direction, _)) = env.sample(u, v)
+    {
+        scattered.change(&rec.p, &direction);
     }
-    let unit_direction = unit_vector(r.direction());
-    let a = 0.5 * (unit_direction.y() + 1.0);
-    (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+    let scattering_pdf = rec.mat.scattering_pdf(r, rec, &scattered);
+    if scattering_pdf <= 0.0 {
+        return (scattered, 0.0);
+    }
+    // Density of the even mix of both strategies
+    let pdf = f64::midpoint(scattering_pdf, env.pdf(scattered.direction()));
+    (scattered, scattering_pdf / pdf)
 }
 
-fn get_ray(cam: &Camera, i: i32, j: i32) -> Ray {
-    let offset = sample_square();
+/// Ray through the point `offset` from the center of pixel `i`, `j`
+fn get_ray(cam: &Camera, i: i32, j: i32, offset: &Vec3, sampler: &mut Sampler) -> Ray {
     let pixel_sample = cam.pixel00_loc
         + ((f64::from(i) + offset.x()) * cam.pixel_delta_u)
         + ((f64::from(j) + offset.y()) * cam.pixel_delta_v);
     let ray_origin = if cam.defocus_angle <= 0.0 {
         &cam.look_from
     } else {
-        &defocus_disk_sample(cam)
+        &defocus_disk_sample(cam, sampler)
     };
     let ray_direction = pixel_sample - ray_origin;
     Ray::new(ray_origin, &ray_direction)
 }
 
-fn defocus_disk_sample(cam: &Camera) -> Point3 {
-    let p = random_in_unit_disk();
+fn defocus_disk_sample(cam: &Camera, sampler: &mut Sampler) -> Point3 {
+    let p = sample_unit_disk(sampler.next_2d());
     cam.look_from + (p[0] * cam.defocus_disk_u) + (p[1] * cam.defocus_disk_v)
 }
 
-fn sample_square() -> Vec3 {
-    Vec3::new(rand_f64() - 0.5, rand_f64() - 0.5, 0.0)
+fn sample_square(sampler: &mut Sampler) -> Vec3 {
+    let (u, v) = sampler.next_2d();
+    Vec3::new(u - 0.5, v - 0.5, 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        aabb::Aabb,
+        film::SAMPLES_AOV,
+        filter::FilterKind,
+        hittable::Hittable,
+        material::{DiffuseLight, Lambertain, Mat},
+        output::encode_image,
+        quad::Quad,
+        progress::SilentProgress,
+        sphere::Sphere,
+        texture::Texture,
+    };
+
+    /// Small camera that renders on several tiles without printing progress
+    fn small_camera() -> Camera {
+        Camera {
+            image_width: 24,
+            samples_per_pixel: 4,
+            max_depth: 4,
+            tile_size: 8,
+            progress: Arc::new(SilentProgress),
+            ..Camera::default()
+        }
+    }
+
+    struct Panics;
+
+    impl Hit for Panics {
+        fn hit<'a>(&'a self, _r: &Ray, _ray_t: &Interval, _rec: &mut HitRecord<'a>) -> bool {
+            panic!("hit panicked");
+        }
+
+        fn bounding_box(&self) -> Aabb {
+            Aabb::from_points(&Point3::new(-1.0, -1.0, -1.0), &Point3::new(1.0, 1.0, 1.0))
+        }
+    }
+
+    /// Diffuse sphere on a ground plane, lit by the default sky
+    fn small_scene() -> HittableList {
+        let mut world = HittableList::new();
+        let mat = |albedo| Mat::Lambertain(Lambertain {
+            albedo: Texture::Solid(albedo),
+        });
+        world.add(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, mat(Color::new(0.7, 0.3, 0.3))));
+        world.add(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, mat(Color::new(0.8, 0.8, 0.0))));
+        world
+    }
+
+    /// Bits of every pixel, with a filter wide enough that tiles overlap where samples are splatted
+    fn render_seeded(seed: u64) -> Vec<[u64; 3]> {
+        let cam = Camera {
+            seed,
+            filter: Filter {
+                kind: FilterKind::Gaussian,
+                radius: 1.5,
+            },
+            ..small_camera()
+        };
+        let image = render_to_buffer(&cam, &small_scene()).expect("valid camera");
+        image.pixels().iter().map(|c| c.e.map(f64::to_bits)).collect()
+    }
+
+    #[test]
+    fn same_seed_renders_identical_images() {
+        assert_eq!(render_seeded(7), render_seeded(7));
+    }
+
+    #[test]
+    fn different_seeds_render_different_images() {
+        assert_ne!(render_seeded(7), render_seeded(8));
+    }
+
+    #[test]
+    fn defocus_disk_spans_defocus_angle() {
+        let mut cam = Camera {
+            defocus_angle: 10.0,
+            focus_dist: 4.0,
+            ..small_camera()
+        };
+        cam.init();
+        let expected = 4.0 * 5.0_f64.to_radians().tan();
+        assert!((cam.defocus_disk_u.len() - expected).abs() < 1e-12);
+        assert!((cam.defocus_disk_v.len() - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "hit panicked")]
+    fn worker_panic_reaches_the_caller() {
+        let mut world = HittableList::new();
+        world.add(Hittable::custom(Panics));
+        render_to_buffer(&small_camera(), &world).ok();
+    }
+
+    fn rejection(cam: &Camera) -> CameraError {
+        cam.validate().expect_err("camera should be rejected")
+    }
+
+    /// Field named by the `CameraError::OutOfRange` that `cam` is rejected with
+    fn out_of_range_field(cam: &Camera) -> &'static str {
+        match rejection(cam) {
+            CameraError::OutOfRange { field, .. } => field,
+            e => panic!("expected an out of range setting, got {e:?}"),
+        }
+    }
+
+    #[test]
+    fn default_camera_is_valid() {
+        assert_eq!(Camera::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_non_finite_aspect_ratio() {
+        for aspect_ratio in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let cam = Camera { aspect_ratio, ..Camera::default() };
+            assert_eq!(rejection(&cam), CameraError::NotFinite { field: "aspect_ratio" });
+        }
+    }
+
+    #[test]
+    fn rejects_zero_image_width() {
+        let cam = Camera { image_width: 0, ..Camera::default() };
+        assert_eq!(out_of_range_field(&cam), "image_width");
+    }
+
+    #[test]
+    fn rejects_zero_samples_per_pixel() {
+        let cam = Camera { samples_per_pixel: 0, ..Camera::default() };
+        assert_eq!(out_of_range_field(&cam), "samples_per_pixel");
+    }
+
+    #[test]
+    fn rejects_vfov_outside_open_half_turn() {
+        for vfov in [0.0, 180.0, 200.0] {
+            let cam = Camera { vfov, ..Camera::default() };
+            assert_eq!(out_of_range_field(&cam), "vfov", "vfov {vfov}");
+        }
+    }
+
+    #[test]
+    fn rejects_defocus_angle_of_half_turn() {
+        for defocus_angle in [180.0, 270.0] {
+            let cam = Camera { defocus_angle, ..Camera::default() };
+            assert_eq!(out_of_range_field(&cam), "defocus_angle", "defocus_angle {defocus_angle}");
+        }
+    }
+
+    #[test]
+    fn rejects_look_from_at_look_at() {
+        let cam = Camera {
+            look_at: Point3::new(0.0, 0.0, 0.0),
+            ..Camera::default()
+        };
+        assert_eq!(rejection(&cam), CameraError::LookFromIsLookAt);
+    }
+
+    #[test]
+    fn rejects_vup_parallel_to_view() {
+        let cam = Camera {
+            vup: Vec3::new(0.0, 0.0, 2.0),
+            ..Camera::default()
+        };
+        assert_eq!(rejection(&cam), CameraError::VupParallelToView);
+    }
+
+    #[test]
+    fn rejects_adaptive_max_below_min() {
+        let cam = Camera {
+            adaptive: Some(AdaptiveSampling {
+                min_samples: 16,
+                max_samples: 8,
+                ..AdaptiveSampling::default()
+            }),
+            ..Camera::default()
+        };
+        assert_eq!(out_of_range_field(&cam), "adaptive.max_samples");
+    }
+
+    #[test]
+    fn render_rejects_invalid_camera() {
+        let cam = Camera { vfov: 0.0, ..small_camera() };
+        let result = render_to_buffer(&cam, &small_scene());
+        assert!(matches!(result, Err(RenderError::InvalidCamera(CameraError::OutOfRange { field: "vfov", .. }))));
+    }
+
+    /// Film of single pixels each given `samples` samples, of luminance 0.5 when `flat` and
+    /// alternating between 0.25 and 0.75 otherwise
+    fn film_of(pixels: &[(u32, bool)]) -> Film {
+        let mut film = Film::new(pixels.len(), 1);
+        for (x, &(samples, flat)) in pixels.iter().enumerate() {
+            let mut pixel = FilmPixel::default();
+            for k in 0..samples {
+                let y = if flat { 0.5 } else { 0.5f64.mul_add(f64::from(k % 2), 0.25) };
+                pixel.add_sample(Color::new(y, y, y));
+            }
+            film.add_tile(&Tile { x0: x, y0: 0, x1: x + 1, y1: 1 }, &[pixel]);
+        }
+        film
+    }
+
+    fn adaptive_camera(min_samples: u32, max_samples: u32) -> Camera {
+        Camera {
+            samples_per_pixel: 4,
+            adaptive: Some(AdaptiveSampling {
+                threshold: 0.01,
+                min_samples,
+                max_samples,
+                heatmap: false,
+            }),
+            ..small_camera()
+        }
+    }
+
+    #[test]
+    fn plan_skips_converged_pixels() {
+        let plan = plan_pass(&adaptive_camera(8, 64), &film_of(&[(8, true), (8, false)]));
+        assert_eq!(plan, [0, 4]);
+    }
+
+    #[test]
+    fn plan_stops_at_max_samples() {
+        let plan = plan_pass(&adaptive_camera(8, 10), &film_of(&[(8, false), (10, false), (9, false)]));
+        assert_eq!(plan, [2, 0, 1]);
+    }
+
+    #[test]
+    fn plan_samples_until_min_samples() {
+        // Pixels with too few samples are sampled even without any spread
+        let plan = plan_pass(&adaptive_camera(8, 64), &film_of(&[(0, true), (4, true), (7, true), (8, true)]));
+        assert_eq!(plan, [4, 4, 4, 0]);
+    }
+
+    #[test]
+    fn adaptive_render_spends_samples_on_noisy_pixels() {
+        let cam = Camera {
+            background: Background::Solid(Color::new(0.5, 0.5, 0.5)),
+            ..adaptive_camera(8, 64)
+        };
+        let image = render_to_buffer(&cam, &small_scene()).expect("valid camera");
+        let samples = image.aov(SAMPLES_AOV).expect("render stores sample counts");
+        // The top row only sees the flat background, the sphere and ground below are noisy
+        assert_eq!(samples[..24], [8.0; 24]);
+        assert!(samples.iter().any(|&n| n > 8.0));
+        assert!(samples.iter().all(|&n| (8.0..=64.0).contains(&n)));
+
+        let heatmap = sample_heatmap(&image).expect("render stores sample counts");
+        let shades: Vec<f64> = heatmap.pixels().iter().map(|c| c.x() + c.y() + c.z()).collect();
+        assert!(shades.iter().any(|&shade| shade > shades[0]));
+    }
+
+    #[test]
+    fn samples_near_tile_edge_reach_neighbouring_tiles() {
+        let mut cam = Camera {
+            filter: Filter {
+                kind: FilterKind::Gaussian,
+                radius: 1.5,
+            },
+            ..small_camera()
+        };
+        cam.init();
+        let world = Bvh::new(small_scene().objects().to_vec());
+        let job = TileJob {
+            pass: 0,
+            tile: Tile { x0: 8, y0: 8, x1: 16, y1: 16 },
+        };
+        let plan = vec![1; 24 * 24];
+        let (region, pixels) = render_tile(&cam, &world, &job, &plan, (24, 24), &|| false);
+        assert_eq!(region, Tile { x0: 7, y0: 7, x1: 17, y1: 17 });
+        for (k, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (region.x0 + k % region.width(), region.y0 + k / region.width());
+            let inside = (8..16).contains(&x) && (8..16).contains(&y);
+            assert!(pixel.splats > 0, "nothing splatted onto ({x}, {y})");
+            assert_eq!(pixel.samples, u32::from(inside), "samples taken in ({x}, {y})");
+        }
+    }
+
+    /// Samples each pixel of `image` received
+    fn samples_of(image: &Image) -> &[f64] {
+        image.aov(SAMPLES_AOV).expect("render stores sample counts")
+    }
+
+    #[test]
+    fn cancelled_render_returns_empty_image() {
+        let cam = small_camera();
+        cam.cancel.cancel();
+        let image = render_to_buffer(&cam, &small_scene()).expect("cancelling is not an error");
+        assert_eq!(samples_of(&image), vec![0.0; image.pixels().len()]);
+        assert!(image.pixels().iter().all(|c| c.e.iter().all(|&x| x.abs() < f64::EPSILON)));
+    }
+
+    #[test]
+    fn cancelling_between_passes_keeps_samples_taken() {
+        let cam = Camera {
+            progressive: true,
+            snapshot_every: Some(SnapshotInterval::Passes(1)),
+            ..small_camera()
+        };
+        let cancel = cam.cancel.clone();
+        let image = render_with_snapshots(&cam, &small_scene(), |_| {
+            cancel.cancel();
+            Ok(())
+        })
+        .expect("cancelling is not an error");
+        assert_eq!(samples_of(&image), vec![1.0; image.pixels().len()]);
+    }
+
+    #[test]
+    fn zero_time_budget_ends_render() {
+        for progressive in [false, true] {
+            let cam = Camera {
+                time_budget: Some(Duration::ZERO),
+                progressive,
+                ..small_camera()
+            };
+            let image = render_to_buffer(&cam, &small_scene()).expect("running out of time is not an error");
+            let most = samples_of(&image).iter().copied().fold(0.0, f64::max);
+            assert!(most <= 4.0, "{most} samples in a pixel");
+        }
+    }
+
+    /// Samples per pixel of each snapshot a progressive render of four passes hands out
+    fn snapshot_samples(every: usize) -> Vec<Vec<f64>> {
+        let cam = Camera {
+            progressive: true,
+            snapshot_every: Some(SnapshotInterval::Passes(every)),
+            ..small_camera()
+        };
+        let mut snapshots = Vec::new();
+        let image = render_with_snapshots(&cam, &small_scene(), |image| {
+            snapshots.push(samples_of(image).to_vec());
+            Ok(())
+        })
+        .expect("valid camera");
+        assert_eq!(samples_of(&image), vec![4.0; image.pixels().len()]);
+        snapshots
+    }
+
+    #[test]
+    fn progressive_snapshots_follow_passes() {
+        // The last pass gives the returned image rather than a snapshot
+        let every_pass = snapshot_samples(1);
+        assert_eq!(every_pass.len(), 3);
+        for (k, samples) in every_pass.iter().enumerate() {
+            assert_eq!(*samples, vec![to_f64(k + 1); samples.len()]);
+        }
+        let every_other = snapshot_samples(2);
+        assert_eq!(every_other.len(), 1);
+        assert_eq!(every_other[0], vec![2.0; every_other[0].len()]);
+        assert_eq!(snapshot_samples(4), Vec::<Vec<f64>>::new());
+    }
+
+    #[test]
+    fn render_rejects_unknown_extension() {
+        let path = std::env::temp_dir().join(format!("rtiaw-unknown-{}.jpg", std::process::id()));
+        let file_name = path.to_str().expect("temp dir is valid UTF-8");
+        match render(&small_camera(), file_name, &small_scene()) {
+            Err(RenderError::UnknownFormat { path: reported }) => assert_eq!(reported, path),
+            other => panic!("expected an unknown format, got {other:?}"),
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn image_size_follows_width_and_aspect_ratio() {
+        for (aspect_ratio, height) in [(16.0 / 9.0, 13), (1.0, 24), (0.5, 48), (100.0, 1)] {
+            let cam = Camera { aspect_ratio, ..small_camera() };
+            let image = render_to_buffer(&cam, &small_scene()).expect("valid camera");
+            assert_eq!((image.width(), image.height()), (24, height), "aspect ratio {aspect_ratio}");
+            assert_eq!(image.pixels().len(), 24 * height);
+        }
+    }
+
+    #[test]
+    fn render_writes_the_rendered_image() {
+        let path = std::env::temp_dir().join(format!("rtiaw-render-{}.pfm", std::process::id()));
+        let file_name = path.to_str().expect("temp dir is valid UTF-8");
+        render(&small_camera(), file_name, &small_scene()).expect("render and write");
+        let written = std::fs::read(&path).expect("render wrote the file");
+        std::fs::remove_file(&path).ok();
+
+        let image = render_to_buffer(&small_camera(), &small_scene()).expect("valid camera");
+        let mut expected = Vec::new();
+        encode_image(&mut expected, &image, ImageFormat::Pfm, &DisplayTransform::default()).expect("writing to memory");
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn render_error_sources() {
+        let io = RenderError::Io {
+            path: PathBuf::from("out.png"),
+            source: io::Error::new(io::ErrorKind::PermissionDenied, "denied"),
+        };
+        let source = io.source().expect("io errors have a source");
+        let inner = source.downcast_ref::<io::Error>().expect("source is the io error");
+        assert_eq!(inner.kind(), io::ErrorKind::PermissionDenied);
+
+        let invalid = RenderError::InvalidCamera(CameraError::LookFromIsLookAt);
+        let source = invalid.source().expect("invalid cameras have a source");
+        assert_eq!(source.downcast_ref::<CameraError>(), Some(&CameraError::LookFromIsLookAt));
+
+        let scene = RenderError::Scene("no such material".into());
+        assert_eq!(scene.source().map(ToString::to_string).as_deref(), Some("no such material"));
+
+        let unknown = RenderError::UnknownFormat {
+            path: PathBuf::from("out.jpg"),
+        };
+        assert!(unknown.source().is_none());
+    }
+
+    #[test]
+    fn render_error_messages() {
+        let io = RenderError::Io {
+            path: PathBuf::from("out.png"),
+            source: io::Error::new(io::ErrorKind::PermissionDenied, "denied"),
+        };
+        assert_eq!(io.to_string(), "out.png: denied");
+        let unknown = RenderError::UnknownFormat {
+            path: PathBuf::from("out.jpg"),
+        };
+        assert_eq!(unknown.to_string(), "out.jpg: unknown image format");
+        let invalid = RenderError::InvalidCamera(CameraError::OutOfRange {
+            field: "image_width",
+            expected: "at least 1",
+        });
+        assert_eq!(invalid.to_string(), "invalid camera: image_width must be at least 1");
+        assert_eq!(RenderError::Scene("no such material".into()).to_string(), "scene error: no such material");
+    }
+
+    #[test]
+    fn camera_error_messages() {
+        assert_eq!(CameraError::NotFinite { field: "vfov" }.to_string(), "vfov must be finite");
+        assert_eq!(CameraError::LookFromIsLookAt.to_string(), "look_from and look_at must differ");
+        assert_eq!(CameraError::VupParallelToView.to_string(), "vup must not be parallel to the view direction");
+    }
+
+    /// Image of a light filling the whole view against a black background, facing the camera
+    /// unless `facing_away`
+    fn render_light(facing_away: bool, two_sided: bool) -> Image {
+        let light = Mat::DiffuseLight(DiffuseLight {
+            emit: Color::new(0.5, 0.25, 1.0),
+            two_sided,
+        });
+        let (corner, u, v) = (Point3::new(-10.0, -10.0, -1.0), Vec3::new(20.0, 0.0, 0.0), Vec3::new(0.0, 20.0, 0.0));
+        let quad = if facing_away { Quad::new(corner, v, u, light) } else { Quad::new(corner, u, v, light) };
+        let mut world = HittableList::new();
+        world.add(quad);
+        let cam = Camera {
+            background: Background::Solid(Color::default()),
+            ..small_camera()
+        };
+        render_to_buffer(&cam, &world).expect("valid camera")
+    }
+
+    #[test]
+    fn lights_are_seen_directly() {
+        for (facing_away, two_sided, expected) in [
+            (false, false, [0.5, 0.25, 1.0]),
+            (true, false, [0.0; 3]),
+            (true, true, [0.5, 0.25, 1.0]),
+        ] {
+            let image = render_light(facing_away, two_sided);
+            for pixel in image.pixels() {
+                let error = (*pixel - Color { e: expected }).len();
+                assert!(error < 1e-12, "{:?} instead of {expected:?}", pixel.e);
+            }
+        }
+    }
+
+    /// Keeps every update a render sends
+    #[derive(Default)]
+    struct Recorder {
+        tiles: Mutex<Vec<Progress>>,
+        finished: Mutex<Vec<Duration>>,
+    }
+
+    impl Recorder {
+        /// Updates for each tile and calls to `finished` so far
+        fn updates(&self) -> (Vec<Progress>, Vec<Duration>) {
+            let tiles = self.tiles.lock().expect("recorder lock").clone();
+            let finished = self.finished.lock().expect("recorder lock").clone();
+            (tiles, finished)
+        }
+    }
+
+    impl ProgressSink for Recorder {
+        fn tile_done(&self, progress: &Progress) {
+            self.tiles.lock().expect("recorder lock").push(*progress);
+        }
+
+        fn finished(&self, elapsed: Duration) {
+            self.finished.lock().expect("recorder lock").push(elapsed);
+        }
+    }
+
+    #[test]
+    fn progress_reports_every_tile_then_finishes() {
+        let recorder = Arc::new(Recorder::default());
+        let cam = Camera {
+            progress: recorder.clone(),
+            ..small_camera()
+        };
+        render_to_buffer(&cam, &small_scene()).expect("valid camera");
+        let (tiles, finished) = recorder.updates();
+        // A 24 pixel square in tiles of 8
+        assert_eq!(tiles.len(), 9);
+        for (k, progress) in tiles.iter().enumerate() {
+            assert_eq!((progress.pass, progress.tiles_done, progress.tiles_total), (0, k + 1, 9));
+        }
+        let mut covered: Vec<_> = tiles.iter().map(|p| (p.tile.y0, p.tile.x0)).collect();
+        covered.sort_unstable();
+        covered.dedup();
+        assert_eq!(covered.len(), 9);
+        assert_eq!(finished.len(), 1);
+        assert!(finished[0] >= tiles[8].elapsed);
+    }
+
+    #[test]
+    fn progressive_progress_counts_passes() {
+        let recorder = Arc::new(Recorder::default());
+        let cam = Camera {
+            progressive: true,
+            progress: recorder.clone(),
+            ..small_camera()
+        };
+        render_to_buffer(&cam, &small_scene()).expect("valid camera");
+        let (tiles, _) = recorder.updates();
+        assert_eq!(tiles.len(), 36);
+        assert!(tiles.iter().all(|p| p.tiles_total == 36));
+        let passes: Vec<usize> = tiles.iter().map(|p| p.pass).collect();
+        assert!(passes.is_sorted());
+        assert_eq!(passes[35], 3);
+    }
 }
@@ -1,27 +1,58 @@
-use crate::{
-    internal::Interval,
-    vec3::Vec3,
-};
+use crate::vec3::Vec3;
 
 pub type Color = Vec3;
 
+/// sRGB encoding of a linear value in `[0, 1]`, the piecewise curve with a linear segment
+/// near black rather than a plain power
 #[inline]
 #[must_use]
-pub fn linear_to_gamma(linear_component: f64) -> f64 {
-    if linear_component > 0_f64 {
-        linear_component.sqrt()
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    if linear_component <= 0.003_130_8 {
+        12.92 * linear_component.max(0.0)
     } else {
-        0_f64
+        1.055f64.mul_add(linear_component.powf(1.0 / 2.4), -0.055)
     }
 }
 
+/// Relative luminance of a linear Rec. 709 color
+#[inline]
 #[must_use]
-pub fn write_color(pixel_color: &Color) -> String {
-    let intensity = Interval::new(0.0, &0.999);
+pub fn luminance(c: &Color) -> f64 {
+    0.0722_f64.mul_add(c.z(), 0.2126_f64.mul_add(c.x(), 0.7152 * c.y()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let rbyte = (256.0 * intensity.clamp(linear_to_gamma(pixel_color.x()))).trunc();
-    let gbyte = (256.0 * intensity.clamp(linear_to_gamma(pixel_color.y()))).trunc();
-    let bbyte = (256.0 * intensity.clamp(linear_to_gamma(pixel_color.z()))).trunc();
+    #[test]
+    fn srgb_is_linear_up_to_breakpoint() {
+        assert!(linear_to_srgb(0.0).abs() < 1e-15);
+        assert!((linear_to_srgb(0.001) - 0.012_92).abs() < 1e-12);
+        assert!((linear_to_srgb(0.003_130_8) - 0.040_449_936).abs() < 1e-12);
+    }
+
+    #[test]
+    fn srgb_segments_meet_at_breakpoint() {
+        let below = linear_to_srgb(0.003_130_8);
+        let above = linear_to_srgb(0.003_130_8_f64.next_up());
+        assert!((above - below).abs() < 1e-6, "{below} then {above}");
+    }
 
-    format!("{rbyte} {gbyte} {bbyte}")
+    #[test]
+    fn srgb_follows_power_curve_above_breakpoint() {
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-12);
+        let x: f64 = 0.18;
+        assert!((linear_to_srgb(x) - 1.055f64.mul_add(x.powf(1.0 / 2.4), -0.055)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn srgb_clamps_negative_values_to_zero() {
+        assert!(linear_to_srgb(-0.5).abs() < 1e-15);
+    }
+
+    #[test]
+    fn luminance_of_white_is_one() {
+        assert!((luminance(&Color::new(1.0, 1.0, 1.0)) - 1.0).abs() < 1e-12);
+    }
 }
@@ -0,0 +1,59 @@
+// Numeric casts the pedantic cast lints reject, for values their callers know to be in range.
+// Float to integer casts truncate toward zero and saturate at the bounds of the target type
+
+#[allow(clippy::cast_precision_loss)]
+pub const fn to_f64(n: usize) -> f64 {
+    n as f64
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub const fn to_f32(x: f64) -> f32 {
+    x as f32
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub const fn to_usize(x: f64) -> usize {
+    x as usize
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub const fn truncate_to_i32(x: f64) -> i32 {
+    x as i32
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub const fn floor_to_i64(x: f64) -> i64 {
+    x.floor() as i64
+}
+
+/// Channel value already scaled to `[0, 255]`
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub const fn float_to_byte(x: f64) -> u8 {
+    x as u8
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+pub const fn to_i32(n: usize) -> i32 {
+    n as i32
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub const fn to_u32(n: usize) -> u32 {
+    n as u32
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub const fn to_u16(x: u32) -> u16 {
+    x as u16
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub const fn to_u8(n: usize) -> u8 {
+    n as u8
+}
+
+/// Lower 32 bits, dropping the rest
+#[allow(clippy::cast_possible_truncation)]
+pub const fn low_bits(x: u64) -> u32 {
+    x as u32
+}
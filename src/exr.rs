@@ -0,0 +1,397 @@
+use std::io::{self, Write};
+
+use crate::{convert::{to_f32, to_i32, to_u16}, image::Image, zlib};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: [u8; 4] = [2, 0, 0, 0]; // Version 2, single part scanline file
+
+/// Storage type of the color channels, AOVs are always written as `Float`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExrPixelType {
+    #[default]
+    Half,  // 16 bit float
+    Float, // 32 bit float
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExrCompression {
+    None,
+    #[default]
+    Zip, // Blocks of 16 scanlines compressed with deflate
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExrOptions {
+    pub pixel_type: ExrPixelType,
+    pub compression: ExrCompression,
+}
+
+impl ExrPixelType {
+    const fn id(self) -> i32 {
+        match self {
+            Self::Half => 1,
+            Self::Float => 2,
+        }
+    }
+}
+
+impl ExrCompression {
+    const fn id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zip => 3,
+        }
+    }
+
+    const fn lines_per_block(self) -> usize {
+        match self {
+            Self::None => 1,
+            Self::Zip => 16,
+        }
+    }
+}
+
+struct Channel<'a> {
+    name: &'a str,
+    pixel_type: ExrPixelType,
+    source: Source<'a>,
+}
+
+enum Source<'a> {
+    Color(usize), // Index into the pixel colors
+    Aov(&'a [f64]),
+}
+
+/// Writes the unclamped linear pixel values of `image` as a scanline `OpenEXR` file with
+/// `R`, `G` and `B` channels, plus a channel for every AOV of the image
+///
+/// # Errors
+///
+/// This function will return an error if writing to `out` fails, the image is empty or too
+/// large, or an AOV name is empty or clashes with another channel
+pub fn write_exr(out: &mut impl Write, image: &Image, options: ExrOptions) -> io::Result<()> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 {
+        return Err(invalid("cannot write an empty image as EXR"));
+    }
+    let x_max = i32::try_from(width - 1).map_err(|_| invalid("image too large for EXR"))?;
+    let y_max = i32::try_from(height - 1).map_err(|_| invalid("image too large for EXR"))?;
+
+    let mut channels: Vec<Channel> = ["R", "G", "B"]
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| Channel {
+            name,
+            pixel_type: options.pixel_type,
+            source: Source::Color(i),
+        })
+        .chain(image.aovs().map(|(name, values)| Channel {
+            name,
+            pixel_type: ExrPixelType::Float,
+            source: Source::Aov(values),
+        }))
+        .collect();
+    // Readers expect the channels sorted by name
+    channels.sort_by(|a, b| a.name.cmp(b.name));
+    if channels.iter().any(|c| c.name.is_empty() || c.name.contains('\0'))
+        || channels.windows(2).any(|w| w[0].name == w[1].name)
+    {
+        return Err(invalid("EXR channel names must be unique and not empty"));
+    }
+
+    let header = header(&channels, options.compression, x_max, y_max);
+    let chunks: Vec<Vec<u8>> = (0..height)
+        .step_by(options.compression.lines_per_block())
+        .map(|y| chunk(image, &channels, options.compression, y))
+        .collect();
+
+    out.write_all(&MAGIC)?;
+    out.write_all(&VERSION)?;
+    out.write_all(&header)?;
+    let mut offset = (MAGIC.len() + VERSION.len() + header.len() + 8 * chunks.len()) as u64;
+    for chunk in &chunks {
+        out.write_all(&offset.to_le_bytes())?;
+        offset += chunk.len() as u64;
+    }
+    for chunk in &chunks {
+        out.write_all(chunk)?;
+    }
+    Ok(())
+}
+
+fn header(channels: &[Channel], compression: ExrCompression, x_max: i32, y_max: i32) -> Vec<u8> {
+    let mut channel_list = Vec::new();
+    for channel in channels {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&channel.pixel_type.id().to_le_bytes());
+        channel_list.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        channel_list.extend_from_slice(&1_i32.to_le_bytes()); // x sampling
+        channel_list.extend_from_slice(&1_i32.to_le_bytes()); // y sampling
+    }
+    channel_list.push(0);
+
+    let window: Vec<u8> = [0, 0, x_max, y_max].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let mut header = Vec::new();
+    attribute(&mut header, "channels", "chlist", &channel_list);
+    attribute(&mut header, "compression", "compression", &[compression.id()]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]); // Increasing y
+    attribute(&mut header, "pixelAspectRatio", "float", &1_f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1_f32.to_le_bytes());
+    header.push(0);
+    header
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&to_i32(value.len()).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Block of scanlines starting at `y`, each stored channel by channel
+fn chunk(image: &Image, channels: &[Channel], compression: ExrCompression, y: usize) -> Vec<u8> {
+    let width = image.width();
+    let rows = y..(y + compression.lines_per_block()).min(image.height());
+    let mut data = Vec::new();
+    for row in rows {
+        let start = row * width;
+        for channel in channels {
+            for i in start..start + width {
+                let value = match channel.source {
+                    Source::Color(c) => image.pixels()[i][c],
+                    Source::Aov(values) => values[i],
+                };
+                match channel.pixel_type {
+                    ExrPixelType::Half => data.extend_from_slice(&f32_to_half(to_f32(value)).to_le_bytes()),
+                    ExrPixelType::Float => data.extend_from_slice(&to_f32(value).to_le_bytes()),
+                }
+            }
+        }
+    }
+    if compression == ExrCompression::Zip {
+        let compressed = zlib::compress(&zip_predict(&data));
+        // Data that does not shrink is stored as is, which readers detect from the size
+        if compressed.len() < data.len() {
+            data = compressed;
+        }
+    }
+
+    let mut chunk = Vec::with_capacity(8 + data.len());
+    chunk.extend_from_slice(&to_i32(y).to_le_bytes());
+    chunk.extend_from_slice(&to_i32(data.len()).to_le_bytes());
+    chunk.extend_from_slice(&data);
+    chunk
+}
+
+/// Splits the bytes into even and odd halves and delta encodes them, as ZIP compression requires
+fn zip_predict(data: &[u8]) -> Vec<u8> {
+    let mut reordered: Vec<u8> = data.iter().step_by(2).chain(data.iter().skip(1).step_by(2)).copied().collect();
+    let mut previous = reordered.first().copied().unwrap_or(0);
+    for byte in reordered.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    reordered
+}
+
+/// Rounds to the nearest 16 bit float, ties to even, overflowing to infinity
+const fn f32_to_half(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = (bits >> 16) & 0x8000;
+    let exponent = (bits >> 23) & 0xff;
+    let mantissa = bits & 0x007f_ffff;
+    let half = if exponent == 0xff {
+        // Infinity, or a quiet NaN
+        0x7c00 | if mantissa == 0 { 0 } else { 0x0200 }
+    } else if exponent > 142 {
+        0x7c00
+    } else if exponent < 113 {
+        // Subnormal, or too small and flushed to zero
+        if exponent < 102 {
+            0
+        } else {
+            round_shift(mantissa | 0x0080_0000, 126 - exponent)
+        }
+    } else {
+        // Rounding may carry into the exponent, which correctly yields the next power of two or infinity
+        round_shift(((exponent - 112) << 23) | mantissa, 13)
+    };
+    to_u16(sign | half)
+}
+
+/// `value >> shift` rounded to nearest, ties to even
+const fn round_shift(value: u32, shift: u32) -> u32 {
+    let truncated = value >> shift;
+    let remainder = value & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    if remainder > halfway || (remainder == halfway && truncated & 1 == 1) {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RenderRng, color::Color};
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+    use rand::{Rng, SeedableRng};
+
+    /// Inverse of `zip_predict`, as readers undo it
+    fn zip_unpredict(data: &[u8]) -> Vec<u8> {
+        let mut deltas = data.to_vec();
+        for i in 1..deltas.len() {
+            deltas[i] = deltas[i - 1].wrapping_add(deltas[i]).wrapping_sub(128);
+        }
+        let (even, odd) = deltas.split_at(data.len().div_ceil(2));
+        let mut out = Vec::with_capacity(data.len());
+        for (i, &byte) in even.iter().enumerate() {
+            out.push(byte);
+            out.extend(odd.get(i));
+        }
+        out
+    }
+
+    fn read_i32(bytes: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes(bytes[at..at + 4].try_into().expect("4 bytes"))
+    }
+
+    fn read_offset(bytes: &[u8], at: usize) -> usize {
+        let offset = u64::from_le_bytes(bytes[at..at + 8].try_into().expect("8 bytes"));
+        usize::try_from(offset).expect("offset fits")
+    }
+
+    /// Null terminated string at `at`, and the position after it
+    fn read_string(bytes: &[u8], at: usize) -> (&str, usize) {
+        let len = bytes[at..].iter().position(|&b| b == 0).expect("null terminator");
+        (std::str::from_utf8(&bytes[at..at + len]).expect("ASCII"), at + len + 1)
+    }
+
+    #[test]
+    fn half_keeps_signed_zero() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+    }
+
+    #[test]
+    fn half_converts_exact_values() {
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(65504.0), 0x7bff); // Largest finite half
+        assert_eq!(f32_to_half(2.0_f32.powi(-14)), 0x0400); // Smallest normal half
+    }
+
+    #[test]
+    fn half_keeps_smallest_subnormal() {
+        assert_eq!(f32_to_half(2.0_f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(-(2.0_f32.powi(-24))), 0x8001);
+        assert_eq!(f32_to_half(2.0_f32.powi(-26)), 0x0000);
+    }
+
+    #[test]
+    fn half_rounds_ties_to_even() {
+        // 1 + 2^-11 is halfway between 1 and the next half, 1 + 3 * 2^-11 between that and the one after
+        assert_eq!(f32_to_half(f32::from_bits(0x3f80_1000)), 0x3c00);
+        assert_eq!(f32_to_half(f32::from_bits(0x3f80_3000)), 0x3c02);
+        // Halfway between zero and the smallest subnormal, and just above it
+        assert_eq!(f32_to_half(2.0_f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(1.5 * 2.0_f32.powi(-25)), 0x0001);
+    }
+
+    #[test]
+    fn half_overflows_to_infinity() {
+        assert_eq!(f32_to_half(65519.0), 0x7bff);
+        assert_eq!(f32_to_half(65520.0), 0x7c00); // Rounds up to the next power of two
+        assert_eq!(f32_to_half(1e10), 0x7c00);
+        assert_eq!(f32_to_half(-1e10), 0xfc00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_half(f32::NEG_INFINITY), 0xfc00);
+    }
+
+    #[test]
+    fn half_keeps_nan() {
+        for nan in [f32::NAN, -f32::NAN, f32::from_bits(0x7f80_0001)] {
+            let half = f32_to_half(nan);
+            assert_eq!(half & 0x7c00, 0x7c00);
+            assert_ne!(half & 0x03ff, 0, "{nan} became infinity");
+        }
+    }
+
+    #[test]
+    fn zip_predict_is_undone_by_reader() {
+        let mut rng = RenderRng::seed_from_u64(3);
+        for len in [0, 1, 2, 3, 100, 1001] {
+            let data: Vec<u8> = (0..len).map(|_| rng.random()).collect();
+            assert_eq!(zip_unpredict(&zip_predict(&data)), data);
+        }
+    }
+
+    #[test]
+    fn file_structure_is_valid() {
+        let (width, height) = (5, 40);
+        let mut image = Image::new(width, height);
+        for (i, pixel) in image.pixels_mut().iter_mut().enumerate() {
+            let v = f64::from(u32::try_from(i).expect("fits"));
+            *pixel = Color::new(v, 0.5 * v, -v);
+        }
+        image.set_aov("depth", vec![1.0; width * height]);
+        let mut bytes = Vec::new();
+        write_exr(&mut bytes, &image, ExrOptions::default()).expect("writing to a Vec cannot fail");
+
+        assert_eq!(bytes[..4], MAGIC);
+        assert_eq!(bytes[4..8], VERSION);
+
+        let mut at = 8;
+        let mut attributes = Vec::new();
+        while bytes[at] != 0 {
+            let (name, next) = read_string(&bytes, at);
+            let (kind, next) = read_string(&bytes, next);
+            let size = usize::try_from(read_i32(&bytes, next)).expect("positive size");
+            attributes.push((name, kind, &bytes[next + 4..next + 4 + size]));
+            at = next + 4 + size;
+        }
+        at += 1;
+        let kind_of = |name: &str| attributes.iter().find(|a| a.0 == name).map(|a| a.1);
+        for (name, kind) in [
+            ("channels", "chlist"),
+            ("compression", "compression"),
+            ("dataWindow", "box2i"),
+            ("displayWindow", "box2i"),
+            ("lineOrder", "lineOrder"),
+            ("pixelAspectRatio", "float"),
+            ("screenWindowCenter", "v2f"),
+            ("screenWindowWidth", "float"),
+        ] {
+            assert_eq!(kind_of(name), Some(kind), "attribute {name}");
+        }
+
+        // Three half channels and a float one, in blocks of 16 scanlines
+        let block_count = height.div_ceil(16);
+        let line_bytes = width * (3 * 2 + 4);
+        let offsets: Vec<usize> = (0..block_count).map(|i| read_offset(&bytes, at + 8 * i)).collect();
+        let mut expected_start = at + 8 * block_count;
+        for (i, &offset) in offsets.iter().enumerate() {
+            assert_eq!(offset, expected_start, "offset of chunk {i}");
+            assert_eq!(read_i32(&bytes, offset), i32::try_from(16 * i).expect("fits"));
+            let size = usize::try_from(read_i32(&bytes, offset + 4)).expect("positive size");
+            let lines = 16.min(height - 16 * i);
+            let data = &bytes[offset + 8..offset + 8 + size];
+            if size < lines * line_bytes {
+                assert_eq!(decompress_to_vec_zlib(data).expect("chunk should inflate").len(), lines * line_bytes);
+            } else {
+                assert_eq!(size, lines * line_bytes);
+            }
+            expected_start = offset + 8 + size;
+        }
+        assert_eq!(expected_start, bytes.len());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    color::{Color, luminance},
+    convert::{to_f64, to_usize},
+    image::Image,
+    tile::Tile,
+};
+
+/// Name of the AOV `Film::to_image` stores the per pixel sample counts in
+pub const SAMPLES_AOV: &str = "samples";
+
+/// Filter weighted total of the samples splatted onto one pixel, along with the count and the
+/// mean and variance of the luminance of the samples taken inside it, kept with Welford's
+/// algorithm
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FilmPixel {
+    pub weighted_sum: Color,
+    pub weight_sum: f64,
+    pub sum: Color, // Unweighted total of the splatted samples, for when their weights cancel out
+    pub splats: u32, // Samples splatted onto the pixel
+    pub samples: u32, // Samples taken inside the pixel, which may be splatted onto others too
+    pub luminance_mean: f64,
+    pub luminance_m2: f64, // Sum of squared differences from the mean
+}
+
+impl FilmPixel {
+    /// Adds a sample to the weighted average shown for the pixel
+    pub fn splat(&mut self, color: Color, weight: f64) {
+        self.weighted_sum += weight * color;
+        self.weight_sum += weight;
+        self.sum += color;
+        self.splats += 1;
+    }
+
+    /// Counts a sample taken inside the pixel towards its statistics
+    pub fn add_sample(&mut self, color: Color) {
+        self.samples += 1;
+        let y = luminance(&color);
+        let delta = y - self.luminance_mean;
+        self.luminance_mean += delta / f64::from(self.samples);
+        self.luminance_m2 = delta.mul_add(y - self.luminance_mean, self.luminance_m2);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.weighted_sum += other.weighted_sum;
+        self.weight_sum += other.weight_sum;
+        self.sum += other.sum;
+        self.splats += other.splats;
+        if other.samples == 0 {
+            return;
+        }
+        if self.samples == 0 {
+            self.samples = other.samples;
+            self.luminance_mean = other.luminance_mean;
+            self.luminance_m2 = other.luminance_m2;
+            return;
+        }
+        let (n_self, n_other) = (f64::from(self.samples), f64::from(other.samples));
+        let n = n_self + n_other;
+        let delta = other.luminance_mean - self.luminance_mean;
+        self.samples += other.samples;
+        self.luminance_mean = delta.mul_add(n_other / n, self.luminance_mean);
+        self.luminance_m2 += (delta * delta).mul_add(n_self * n_other / n, other.luminance_m2);
+    }
+
+    /// Standard error of the mean luminance relative to the mean, infinite with fewer than
+    /// two samples
+    #[must_use]
+    pub fn relative_error(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+        let n = f64::from(self.samples);
+        let standard_error = (self.luminance_m2.max(0.0) / (n - 1.0) / n).sqrt();
+        if standard_error <= 0.0 {
+            0.0
+        } else {
+            standard_error / self.luminance_mean.abs()
+        }
+    }
+
+    /// Weighted average of the splatted samples. Filters with negative lobes can leave the
+    /// weights adding up to nothing or less, the plain average is used then, and black if no
+    /// samples were splatted at all
+    #[must_use]
+    pub fn mean(&self) -> Color {
+        if self.weight_sum > 0.0 {
+            self.weighted_sum / self.weight_sum
+        } else if self.splats > 0 {
+            self.sum / f64::from(self.splats)
+        } else {
+            Color::default()
+        }
+    }
+}
+
+/// Accumulation buffer that rendering passes add their samples into
+#[derive(Clone, Debug, Default)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    #[must_use]
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![FilmPixel::default(); width * height],
+        }
+    }
+
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    #[must_use]
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    #[must_use]
+    pub fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    /// Adds the pixels of a rendered tile, given row by row. The tile may include a border of
+    /// pixels around the one rendered that its samples were splatted onto
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tile lies outside the film or `pixels` does not cover it
+    pub fn add_tile(&mut self, tile: &Tile, pixels: &[FilmPixel]) {
+        assert!(tile.x1 <= self.width && tile.y1 <= self.height, "tile outside the film");
+        assert_eq!(pixels.len(), tile.pixel_count(), "tile pixel count mismatch");
+        for (row, samples) in (tile.y0..tile.y1).zip(pixels.chunks(tile.width().max(1))) {
+            let start = row * self.width;
+            for (pixel, sample) in self.pixels[start + tile.x0..start + tile.x1].iter_mut().zip(samples) {
+                pixel.merge(sample);
+            }
+        }
+    }
+
+    /// Mean of every pixel, with the sample counts in the `SAMPLES_AOV` AOV
+    ///
+    /// # Panics
+    ///
+    /// Only if the pixel buffer no longer matches the dimensions, which `Film::new` sizes it to
+    #[must_use]
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::from_pixels(self.width, self.height, self.pixels.iter().map(FilmPixel::mean).collect())
+            .expect("film dimensions match its pixel buffer");
+        image.set_aov(SAMPLES_AOV, self.pixels.iter().map(|p| f64::from(p.samples)).collect());
+        image
+    }
+}
+
+/// Sample counts of an image rendered from a `Film`, shaded from black through red and yellow
+/// to white at the highest count. `None` if the image has no `SAMPLES_AOV` AOV
+#[must_use]
+pub fn sample_heatmap(image: &Image) -> Option<Image> {
+    const RAMP: [Color; 4] = [
+        Color::new(0.0, 0.0, 0.0),
+        Color::new(1.0, 0.0, 0.0),
+        Color::new(1.0, 1.0, 0.0),
+        Color::new(1.0, 1.0, 1.0),
+    ];
+    let samples = image.aov(SAMPLES_AOV)?;
+    let most = samples.iter().copied().fold(0.0, f64::max).max(1.0);
+    let last = to_f64(RAMP.len() - 1);
+    let pixels = samples
+        .iter()
+        .map(|&n| {
+            let x = (n / most).clamp(0.0, 1.0) * last;
+            let k = x.floor().min(last - 1.0);
+            let (low, high) = (RAMP[to_usize(k)], RAMP[to_usize(k) + 1]);
+            low + (x - k) * (high - low)
+        })
+        .collect();
+    Image::from_pixels(image.width(), image.height(), pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel_of(luminances: &[f64]) -> FilmPixel {
+        let mut pixel = FilmPixel::default();
+        for &y in luminances {
+            pixel.add_sample(Color::new(y, y, y));
+        }
+        pixel
+    }
+
+    #[test]
+    fn relative_error_is_infinite_below_two_samples() {
+        assert!(pixel_of(&[]).relative_error().is_infinite());
+        assert!(pixel_of(&[0.5]).relative_error().is_infinite());
+    }
+
+    #[test]
+    fn relative_error_is_zero_without_spread() {
+        assert!(pixel_of(&[0.3; 5]).relative_error().abs() < 1e-12);
+    }
+
+    #[test]
+    fn relative_error_is_standard_error_over_mean() {
+        // Sample variance 1 / 3, so a standard error of sqrt(1 / 12) around a mean of 1.5
+        let error = pixel_of(&[1.0, 2.0, 1.0, 2.0]).relative_error();
+        assert!((error - (1.0_f64 / 12.0).sqrt() / 1.5).abs() < 1e-12, "{error}");
+    }
+
+    #[test]
+    fn merge_matches_adding_every_sample() {
+        let luminances = [0.1, 0.7, 0.4, 0.9, 0.2];
+        let mut merged = pixel_of(&luminances[..2]);
+        merged.merge(&pixel_of(&luminances[2..]));
+        let whole = pixel_of(&luminances);
+        assert_eq!(merged.samples, whole.samples);
+        assert!((merged.luminance_mean - whole.luminance_mean).abs() < 1e-12);
+        assert!((merged.luminance_m2 - whole.luminance_m2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn mean_is_weighted_average() {
+        let mut pixel = FilmPixel::default();
+        pixel.splat(Color::new(1.0, 0.0, 0.0), 3.0);
+        pixel.splat(Color::new(0.0, 1.0, 0.0), 1.0);
+        assert_eq!(pixel.mean().e, [0.75, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn mean_without_positive_weight_is_plain_average() {
+        // Negative lobes outweighing the rest, and cancelling out exactly
+        for weights in [[0.2, -0.5], [0.5, -0.5]] {
+            let mut pixel = FilmPixel::default();
+            pixel.splat(Color::new(1.0, 0.0, 0.0), weights[0]);
+            pixel.splat(Color::new(0.0, 1.0, 0.0), weights[1]);
+            assert_eq!(pixel.mean().e, [0.5, 0.5, 0.0], "weights {weights:?}");
+        }
+    }
+
+    #[test]
+    fn mean_without_samples_is_black() {
+        assert_eq!(FilmPixel::default().mean().e, [0.0; 3]);
+    }
+}
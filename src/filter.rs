@@ -0,0 +1,141 @@
+use std::f64::consts::PI;
+
+use crate::convert::to_usize;
+
+/// Shape of a pixel reconstruction filter
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FilterKind {
+    #[default]
+    Box,      // Equal weight inside the radius, a plain average for a radius of half a pixel
+    Tent,     // Falls off linearly to zero at the radius
+    Gaussian, // Bell curve with a standard deviation of a third of the radius, shifted and scaled to span 1 to 0
+    Mitchell, // Mitchell-Netravali cubic with B = C = 1/3, sharper than a Gaussian with slight ringing
+    Lanczos,  // Sinc windowed by a wider sinc, sharpest of all with the most ringing
+}
+
+/// Weighting of the samples splatted onto each pixel near them. Separable, the weight of a
+/// sample is the product of the weights of its horizontal and vertical distances
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    pub radius: f64, // Distance in pixels past which samples get no weight
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            kind: FilterKind::Box,
+            radius: 0.5,
+        }
+    }
+}
+
+impl Filter {
+    /// Weight of a sample `dx` and `dy` pixels from the center of a pixel, 1 at the center and
+    /// 0 beyond the radius
+    #[must_use]
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    /// Pixels beyond its own that a sample inside a pixel can reach in each direction
+    #[must_use]
+    pub fn border(&self) -> usize {
+        to_usize((self.radius - 0.5).ceil().max(0.0))
+    }
+
+    fn weight_1d(&self, x: f64) -> f64 {
+        let r = self.radius;
+        if x.abs() > r {
+            return 0.0;
+        }
+        match self.kind {
+            // Half open, so a sample on the edge between two pixels only counts for one
+            FilterKind::Box => f64::from(u8::from(x > -r)),
+            FilterKind::Tent => 1.0 - x.abs() / r,
+            FilterKind::Gaussian => {
+                let gaussian = |x: f64| (-4.5 * (x / r).powi(2)).exp();
+                (gaussian(x) - gaussian(r)) / (1.0 - gaussian(r))
+            }
+            FilterKind::Mitchell => mitchell(2.0 * x / r),
+            FilterKind::Lanczos => sinc(x) * sinc(x / r),
+        }
+    }
+}
+
+/// Mitchell-Netravali cubic with B = C = 1/3, which reaches zero at 2. The coefficients are
+/// those of the general form with B and C substituted, scaled to 1 at the center
+fn mitchell(x: f64) -> f64 {
+    let x = x.abs();
+    let value = if x < 1.0 {
+        7.0f64.mul_add(x, -12.0).mul_add(x * x, 16.0 / 3.0)
+    } else if x < 2.0 {
+        (-7.0 / 3.0f64).mul_add(x, 12.0).mul_add(x, -20.0).mul_add(x, 32.0 / 3.0)
+    } else {
+        0.0
+    };
+    value * 3.0 / 16.0
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::to_f64;
+
+    const KINDS: [FilterKind; 5] =
+        [FilterKind::Box, FilterKind::Tent, FilterKind::Gaussian, FilterKind::Mitchell, FilterKind::Lanczos];
+    const RADII: [f64; 5] = [0.5, 1.0, 1.5, 2.0, 2.7];
+
+    fn filters() -> impl Iterator<Item = Filter> {
+        KINDS.into_iter().flat_map(|kind| RADII.into_iter().map(move |radius| Filter { kind, radius }))
+    }
+
+    #[test]
+    fn weight_is_one_at_center() {
+        for filter in filters() {
+            assert!((filter.weight(0.0, 0.0) - 1.0).abs() < 1e-12, "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn weight_is_zero_beyond_radius() {
+        for filter in filters() {
+            let outside = filter.radius + 1e-9;
+            for (dx, dy) in [(outside, 0.0), (-outside, 0.0), (0.0, outside), (0.0, -outside), (outside, outside)] {
+                assert!(filter.weight(dx, dy).abs() < 1e-12, "{filter:?} at ({dx}, {dy})");
+            }
+        }
+    }
+
+    #[test]
+    fn weight_is_symmetric() {
+        for filter in filters().filter(|f| f.kind != FilterKind::Box) {
+            for x in [0.1, 0.4, 0.9, 1.7] {
+                assert!((filter.weight(x, 0.3) - filter.weight(-x, -0.3)).abs() < 1e-12, "{filter:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn border_covers_splat_radius() {
+        for filter in filters() {
+            let border = to_f64(filter.border());
+            // A sample anywhere in its pixel reaches no pixel past the border
+            for k in 0..10 {
+                let offset = f64::from(k) / 10.0 - 0.5;
+                assert!(filter.weight(border + 1.0 - offset, 0.0).abs() < 1e-12, "{filter:?}");
+                assert!(filter.weight(-border - 1.0 - offset, 0.0).abs() < 1e-12, "{filter:?}");
+            }
+            // But can reach the last pixel of the border
+            assert!(border == 0.0 || filter.radius > border - 0.5, "{filter:?}");
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{color::Color, image::Image};
+
+const MAX_PIXELS: usize = 1 << 28; // Far beyond any real environment map, guards against bogus headers
+
+#[derive(Debug)]
+pub enum HdrError {
+    Io { path: PathBuf, source: io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Format { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for HdrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Format { .. } => None,
+        }
+    }
+}
+
+/// Loads a Radiance `.hdr` (RGBE) image as linear radiance.
+/// Flat, old style run length and adaptive run length encoded scanlines are supported
+///
+/// # Errors
+///
+/// This function will return an error if the file cannot be read, is not an RGBE image,
+/// is truncated, or declares more than `MAX_PIXELS` pixels
+pub fn load_hdr(path: impl AsRef<Path>) -> Result<Image, HdrError> {
+    let path = path.as_ref();
+    let io_error = |source| HdrError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let format_error = |message: &str| HdrError::Format {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    };
+    let mut reader = BufReader::new(File::open(path).map_err(io_error)?);
+
+    let magic = read_line(&mut reader).map_err(io_error)?;
+    if !magic.starts_with("#?") {
+        return Err(format_error("missing #? signature, not a Radiance file"));
+    }
+    loop {
+        let line = read_line(&mut reader).map_err(io_error)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=")
+            && format != "32-bit_rle_rgbe"
+        {
+            return Err(format_error(&format!("unsupported pixel format {format}")));
+        }
+    }
+
+    let resolution = read_line(&mut reader).map_err(io_error)?;
+    let (width, height, flip) = parse_resolution(&resolution)
+        .ok_or_else(|| format_error(&format!("unsupported resolution line '{resolution}'")))?;
+    if width == 0 || height == 0 {
+        return Err(format_error("image has no pixels"));
+    }
+    let pixel_count = width
+        .checked_mul(height)
+        .filter(|&n| n <= MAX_PIXELS)
+        .ok_or_else(|| format_error(&format!("image of {width}x{height} pixels is too large")))?;
+
+    let mut pixels = Vec::with_capacity(pixel_count);
+    let mut scanline = vec![[0_u8; 4]; width];
+    for _ in 0..height {
+        read_scanline(&mut reader, &mut scanline).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => format_error(&e.to_string()),
+            io::ErrorKind::UnexpectedEof => format_error("file ends before the last scanline"),
+            _ => io_error(e),
+        })?;
+        pixels.extend(scanline.iter().copied().map(rgbe_to_color));
+    }
+    if flip {
+        let rows: Vec<&[Color]> = pixels.chunks(width).rev().collect();
+        pixels = rows.concat();
+    }
+    Image::from_pixels(width, height, pixels).ok_or_else(|| format_error("pixel count mismatch"))
+}
+
+/// Reads a header line without its newline, the header is ASCII
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut buf = Vec::new();
+    reader.read_until(b'\n', &mut buf)?;
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+    }
+    Ok(String::from_utf8_lossy(&buf).trim_end().to_string())
+}
+
+/// Parses `-Y height +X width` (top to bottom) or `+Y height +X width` (bottom to top)
+fn parse_resolution(line: &str) -> Option<(usize, usize, bool)> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let [y_axis, height, "+X", width] = tokens.as_slice() else {
+        return None;
+    };
+    let flip = match *y_axis {
+        "-Y" => false,
+        "+Y" => true,
+        _ => return None,
+    };
+    Some((width.parse().ok()?, height.parse().ok()?, flip))
+}
+
+fn read_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut first = [0_u8; 4];
+    reader.read_exact(&mut first)?;
+    let adaptive_rle = (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0;
+    if !adaptive_rle {
+        return read_flat_scanline(reader, scanline, first);
+    }
+    if usize::from(first[2]) << 8 | usize::from(first[3]) != width {
+        return Err(invalid_data("scanline width mismatch"));
+    }
+
+    // Each channel is stored separately as runs and literal spans
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0_u8; 1];
+            reader.read_exact(&mut count)?;
+            let (run, count) = if count[0] > 128 {
+                (true, usize::from(count[0] - 128))
+            } else {
+                (false, usize::from(count[0]))
+            };
+            if count == 0 || x + count > width {
+                return Err(invalid_data("bad scanline run length"));
+            }
+            if run {
+                let mut value = [0_u8; 1];
+                reader.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+            } else {
+                let mut values = vec![0_u8; count];
+                reader.read_exact(&mut values)?;
+                for (pixel, value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+            }
+            x += count;
+        }
+    }
+    Ok(())
+}
+
+/// Plain RGBE pixels, where a `1 1 1 n` pixel repeats the previous one
+fn read_flat_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]], first: [u8; 4]) -> io::Result<()> {
+    let mut pixel = first;
+    let mut x = 0;
+    let mut shift = 0;
+    loop {
+        if pixel[0] == 1 && pixel[1] == 1 && pixel[2] == 1 {
+            if shift > 16 {
+                return Err(invalid_data("bad scanline run length"));
+            }
+            let count = usize::from(pixel[3]) << shift;
+            if x == 0 || x + count > scanline.len() {
+                return Err(invalid_data("bad scanline run length"));
+            }
+            let previous = scanline[x - 1];
+            scanline[x..x + count].fill(previous);
+            x += count;
+            shift += 8;
+        } else {
+            scanline[x] = pixel;
+            x += 1;
+            shift = 0;
+        }
+        if x >= scanline.len() {
+            return Ok(());
+        }
+        reader.read_exact(&mut pixel)?;
+    }
+}
+
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::default();
+    }
+    let scale = 2_f64.powi(i32::from(rgbe[3]) - (128 + 8));
+    Color::new(
+        (f64::from(rgbe[0]) + 0.5) * scale,
+        (f64::from(rgbe[1]) + 0.5) * scale,
+        (f64::from(rgbe[2]) + 0.5) * scale,
+    )
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const HEADER: &[u8] = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n";
+
+    /// Writes `bytes` to a temporary file named after `name` and loads it
+    fn load_bytes(name: &str, bytes: &[u8]) -> Result<Image, HdrError> {
+        let path = std::env::temp_dir().join(format!("rtiaw-hdr-{name}-{}.hdr", std::process::id()));
+        fs::write(&path, bytes).expect("temporary file should be writable");
+        let result = load_hdr(&path);
+        fs::remove_file(&path).ok();
+        result
+    }
+
+    fn file(resolution: &str, pixels: &[u8]) -> Vec<u8> {
+        [HEADER, resolution.as_bytes(), b"\n", pixels].concat()
+    }
+
+    fn scanline(width: usize, bytes: &[u8]) -> io::Result<Vec<[u8; 4]>> {
+        let mut scanline = vec![[0; 4]; width];
+        read_scanline(&mut &bytes[..], &mut scanline)?;
+        Ok(scanline)
+    }
+
+    #[test]
+    fn reads_flat_scanline() {
+        let bytes = [128, 64, 0, 129, 1, 2, 3, 130];
+        assert_eq!(scanline(2, &bytes).expect("valid scanline"), [[128, 64, 0, 129], [1, 2, 3, 130]]);
+    }
+
+    #[test]
+    fn reads_old_style_runs() {
+        // A pixel repeated 3 times, then 2 << 8 more
+        let mut bytes = vec![10, 20, 30, 128, 1, 1, 1, 3, 1, 1, 1, 2];
+        bytes.extend([40, 50, 60, 128]);
+        let pixels = scanline(4 + 512 + 1, &bytes).expect("valid scanline");
+        assert!(pixels[..516].iter().all(|&p| p == [10, 20, 30, 128]));
+        assert_eq!(pixels[516], [40, 50, 60, 128]);
+    }
+
+    #[test]
+    fn reads_adaptive_runs() {
+        // Width 10, each channel as a run of 4 then a literal span of 6
+        let mut bytes = vec![2, 2, 0, 10];
+        for channel in 0..4 {
+            bytes.extend([128 + 4, channel]);
+            bytes.push(6);
+            bytes.extend((0..6).map(|x| 10 * channel + x));
+        }
+        let pixels = scanline(10, &bytes).expect("valid scanline");
+        assert_eq!(pixels[0], [0, 1, 2, 3]);
+        assert_eq!(pixels[3], [0, 1, 2, 3]);
+        assert_eq!(pixels[4], [0, 10, 20, 30]);
+        assert_eq!(pixels[9], [5, 15, 25, 35]);
+    }
+
+    #[test]
+    fn rejects_run_past_scanline_end() {
+        let bytes = [2, 2, 0, 8, 128 + 9, 1];
+        assert_eq!(scanline(8, &bytes).map_err(|e| e.kind()), Err(io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn loads_image_bottom_to_top() {
+        // Exponent 129 scales by 2^-7, mantissas are offset by half a step
+        let pixels = [[63, 0, 0, 129], [0, 63, 0, 129]].concat();
+        let image = load_bytes("flip", &file("+Y 2 +X 1", &pixels)).expect("valid file");
+        assert_eq!((image.width(), image.height()), (1, 2));
+        assert_eq!(image.get(0, 0).e, [0.5 / 128.0, 63.5 / 128.0, 0.5 / 128.0]);
+        assert_eq!(image.get(0, 1).e, [63.5 / 128.0, 0.5 / 128.0, 0.5 / 128.0]);
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let pixels = [[63, 0, 0, 129], [0, 63, 0, 129]].concat();
+        let result = load_bytes("truncated", &file("-Y 3 +X 1", &pixels));
+        assert!(matches!(result, Err(HdrError::Format { .. })), "{result:?}");
+    }
+
+    #[test]
+    fn rejects_huge_resolution() {
+        let huge = format!("-Y {} +X {}", usize::MAX / 2, 4);
+        assert!(matches!(load_bytes("huge", &file(&huge, &[])), Err(HdrError::Format { .. })));
+        let large = format!("-Y {} +X {}", 1 << 15, 1 << 14);
+        assert!(matches!(load_bytes("large", &file(&large, &[])), Err(HdrError::Format { .. })));
+    }
+}
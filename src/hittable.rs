@@ -1,43 +1,70 @@
+use std::sync::Arc;
+
 use crate::{
+    aabb::Aabb,
     color::Color,
-    material::Mat,
+    internal::Interval,
+    material::{Lambertain, Mat},
+    mesh::TriangleMesh,
+    quad::Quad,
     ray::Ray,
+    sphere::Sphere,
+    texture::Texture,
+    triangle::Triangle,
     vec3::{Point3, Vec3, dot},
 };
 
-pub struct HitRecord {
+/// Material reported by a `HitRecord` that has not been filled in by a hit
+static DEFAULT_MAT: Mat = Mat::Lambertain(Lambertain {
+    albedo: Texture::Solid(Color::new(0.0, 0.0, 0.0)),
+});
+
+/// Something a ray can intersect, implement this to add new primitives
+pub trait Hit: Send + Sync {
+    /// Records the closest intersection inside `ray_t` in `rec`, returns false on a miss.
+    /// Callers pass a fresh record and only read it after a hit, so `rec` may be left partly
+    /// written on a miss
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool;
+
+    /// Box enclosing the whole object, `Aabb::EMPTY` for objects that can never be hit
+    fn bounding_box(&self) -> Aabb;
+}
+
+pub struct HitRecord<'a> {
     pub p: Point3,
     pub normal: Vec3,
     pub t: f64,
+    pub u: f64, // Surface coordinates of the hit point
+    pub v: f64,
     pub front_face: bool,
-    pub mat: Mat,
+    pub mat: &'a Mat,
 }
 
-impl Default for HitRecord {
+impl Default for HitRecord<'_> {
     fn default() -> Self {
         Self {
             p: Point3::default(),
             normal: Vec3::default(),
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
             front_face: true,
-            mat: Mat::Lambertain {
-                albedo: Color::default(),
-            },
+            mat: &DEFAULT_MAT,
         }
     }
 }
 
-impl HitRecord {
+impl HitRecord<'_> {
     #[must_use]
     pub fn new(p: Point3, normal: Vec3, t: f64) -> Self {
         Self {
             p,
             normal,
             t,
+            u: 0.0,
+            v: 0.0,
             front_face: true,
-            mat: Mat::Lambertain {
-                albedo: Color::default(),
-            },
+            mat: &DEFAULT_MAT,
         }
     }
 
@@ -50,3 +77,146 @@ impl HitRecord {
         };
     }
 }
+
+/// Built in primitives, plus `Custom` for anything implementing `Hit`
+#[derive(Clone)]
+pub enum Hittable {
+    Sphere(Sphere),
+    Quad(Quad),
+    Triangle(Triangle),
+    Mesh(Arc<TriangleMesh>),
+    Custom(Arc<dyn Hit>),
+    Empty,
+}
+
+impl Hittable {
+    /// Wraps a user defined primitive
+    pub fn custom(object: impl Hit + 'static) -> Self {
+        Self::Custom(Arc::new(object))
+    }
+}
+
+impl Hit for Hittable {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        match self {
+            Self::Sphere(sphere) => sphere.hit(r, ray_t, rec),
+            Self::Quad(quad) => quad.hit(r, ray_t, rec),
+            Self::Triangle(triangle) => triangle.hit(r, ray_t, rec),
+            Self::Mesh(mesh) => mesh.hit(r, ray_t, rec),
+            Self::Custom(object) => object.hit(r, ray_t, rec),
+            Self::Empty => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            Self::Sphere(sphere) => sphere.bounding_box(),
+            Self::Quad(quad) => quad.bounding_box(),
+            Self::Triangle(triangle) => triangle.bounding_box(),
+            Self::Mesh(mesh) => mesh.bounding_box(),
+            Self::Custom(object) => object.bounding_box(),
+            Self::Empty => Aabb::EMPTY,
+        }
+    }
+}
+
+impl From<Sphere> for Hittable {
+    fn from(sphere: Sphere) -> Self {
+        Self::Sphere(sphere)
+    }
+}
+
+impl From<Quad> for Hittable {
+    fn from(quad: Quad) -> Self {
+        Self::Quad(quad)
+    }
+}
+
+impl From<Triangle> for Hittable {
+    fn from(triangle: Triangle) -> Self {
+        Self::Triangle(triangle)
+    }
+}
+
+impl From<TriangleMesh> for Hittable {
+    fn from(mesh: TriangleMesh) -> Self {
+        Self::Mesh(Arc::new(mesh))
+    }
+}
+
+impl From<Arc<dyn Hit>> for Hittable {
+    fn from(object: Arc<dyn Hit>) -> Self {
+        Self::Custom(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bvh::Bvh, hittable_list::HittableList};
+
+    /// Plane z = `z` facing +z, limited to a square so it has a bounding box
+    struct Wall {
+        z: f64,
+    }
+
+    impl Hit for Wall {
+        fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+            let t = (self.z - r.origin().z()) / r.direction().z();
+            let p = r.at(t);
+            if !ray_t.surrounds(t) || p.x().abs() > 10.0 || p.y().abs() > 10.0 {
+                return false;
+            }
+            rec.t = t;
+            rec.p = p;
+            rec.set_face_normal(r, Vec3::new(0.0, 0.0, 1.0));
+            true
+        }
+
+        fn bounding_box(&self) -> Aabb {
+            Aabb::from_points(&Point3::new(-10.0, -10.0, self.z), &Point3::new(10.0, 10.0, self.z)).pad_to_minimums()
+        }
+    }
+
+    fn closest_hit(world: &impl Hit, origin: &Point3, direction: &Vec3) -> Option<(f64, bool)> {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(origin, direction);
+        world.hit(&r, &Interval::new(0.001, &f64::INFINITY), &mut rec).then_some((rec.t, rec.front_face))
+    }
+
+    #[test]
+    fn custom_objects_are_hit_through_the_enum() {
+        let wall = Hittable::custom(Wall { z: -3.0 });
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        assert_eq!(closest_hit(&wall, &Point3::default(), &down), Some((3.0, true)));
+        assert_eq!(closest_hit(&wall, &Point3::new(20.0, 0.0, 0.0), &down), None);
+        let bbox = wall.bounding_box();
+        assert_eq!([bbox.x.min, bbox.x.max], [-10.0, 10.0]);
+    }
+
+    #[test]
+    fn custom_objects_mix_with_built_in_ones() {
+        let mut world = HittableList::new();
+        world.add(Hittable::custom(Wall { z: -3.0 }));
+        world.add(Hittable::custom(Wall { z: -6.0 }));
+        world.add(Hittable::Empty);
+        let shared: Arc<dyn Hit> = Arc::new(Wall { z: -1.5 });
+        let bvh = Bvh::new(vec![shared.into(), Hittable::custom(Wall { z: -6.0 })]);
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        assert_eq!(closest_hit(&world, &Point3::default(), &down), Some((3.0, true)));
+        assert_eq!(closest_hit(&bvh, &Point3::default(), &down), Some((1.5, true)));
+        assert_eq!(closest_hit(&world, &Point3::new(0.0, 0.0, -10.0), &-down), Some((4.0, false)));
+    }
+
+    #[test]
+    fn face_normal_opposes_the_ray() {
+        let outward = Vec3::new(0.0, 1.0, 0.0);
+        let mut rec = HitRecord::new(Point3::default(), outward, 1.0);
+        rec.set_face_normal(&Ray::new(&Point3::new(0.0, 1.0, 0.0), &Vec3::new(0.0, -1.0, 0.0)), outward);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal.e, [0.0, 1.0, 0.0]);
+        rec.set_face_normal(&Ray::new(&Point3::new(0.0, -1.0, 0.0), &Vec3::new(0.0, 1.0, 0.0)), outward);
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal.e, [0.0, -1.0, 0.0]);
+    }
+}
@@ -1,60 +1,261 @@
 use crate::{
-    hittable::HitRecord,
+    aabb::Aabb,
+    hittable::{Hit, HitRecord, Hittable},
     internal::Interval,
     ray::Ray,
-    sphere::Hittable,
 };
 
-pub struct HittableList<const L: usize> {
+/// Growable list of objects making up a scene
+#[derive(Default)]
+pub struct HittableList {
+    objects: Vec<Hittable>,
+}
+
+impl HittableList {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { objects: Vec::new() }
+    }
+
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            objects: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+
+    pub fn add(&mut self, object: impl Into<Hittable>) {
+        self.objects.push(object.into());
+    }
+
+    /// Removes and returns the object at `index`, shifting the later objects down
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds
+    pub fn remove(&mut self, index: usize) -> Hittable {
+        self.objects.remove(index)
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    #[must_use]
+    pub fn objects(&self) -> &[Hittable] {
+        &self.objects
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Hittable> {
+        self.objects.iter()
+    }
+}
+
+impl Hit for HittableList {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        hit_all(&self.objects, r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        bounding_box_all(&self.objects)
+    }
+}
+
+impl<H: Into<Hittable>> Extend<H> for HittableList {
+    fn extend<T: IntoIterator<Item = H>>(&mut self, iter: T) {
+        self.objects.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+impl<H: Into<Hittable>> FromIterator<H> for HittableList {
+    fn from_iter<T: IntoIterator<Item = H>>(iter: T) -> Self {
+        Self {
+            objects: iter.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl IntoIterator for HittableList {
+    type Item = Hittable;
+    type IntoIter = std::vec::IntoIter<Hittable>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.objects.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a HittableList {
+    type Item = &'a Hittable;
+    type IntoIter = std::slice::Iter<'a, Hittable>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.objects.iter()
+    }
+}
+
+impl<const L: usize> From<&FixedHittableList<L>> for HittableList {
+    fn from(list: &FixedHittableList<L>) -> Self {
+        list.objects().iter().cloned().collect()
+    }
+}
+
+/// Fixed capacity list of objects that never allocates
+pub struct FixedHittableList<const L: usize> {
     i: usize,
     objects: [Hittable; L],
 }
 
-impl<const L: usize> Default for HittableList<L> {
+impl<const L: usize> Default for FixedHittableList<L> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<const L: usize> HittableList<L> {
+impl<const L: usize> FixedHittableList<L> {
     pub fn clear(&mut self) {
         self.i = 0;
         self.objects.fill(Hittable::Empty);
     }
     #[must_use]
     pub const fn new() -> Self{
-        let objects = [Hittable::Empty; L];
+        let objects = [const { Hittable::Empty }; L];
         let i = 0;
         Self{i,objects}
     }
+
+    /// The objects added so far, without the unused slots
+    #[must_use]
+    pub fn objects(&self) -> &[Hittable] {
+        &self.objects[..self.i]
+    }
 }
 
-impl<const L: usize> HittableList<L> {
+impl<const L: usize> FixedHittableList<L> {
     /// .
     ///
     /// # Panics
     ///
     /// Panics if you add more items than space is allocated
-    pub fn add(&mut self, object: Hittable) {
+    pub fn add(&mut self, object: impl Into<Hittable>) {
         assert!(self.i < self.objects.len(), "out of bounds");
-        self.objects[self.i] = object;
+        self.objects[self.i] = object.into();
         self.i += 1;
     }
 }
 
-impl<const L: usize> HittableList<L> {
-    pub fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
-        let mut hit_anything = false;
-        let mut closest_so_far = ray_t.max;
-        for object in &self.objects {
-            let mut temp_rec = HitRecord::default();
-            let temp_ray_t = Interval::new(ray_t.min, &closest_so_far);
-            if object.hit(r, &temp_ray_t, &mut temp_rec) {
-                hit_anything = true;
-                closest_so_far = temp_rec.t;
-                *rec = temp_rec;
-            }
+impl<const L: usize> Hit for FixedHittableList<L> {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        hit_all(self.objects(), r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        bounding_box_all(self.objects())
+    }
+}
+
+fn bounding_box_all(objects: &[Hittable]) -> Aabb {
+    objects
+        .iter()
+        .fold(Aabb::EMPTY, |acc, o| Aabb::surrounding(&acc, &o.bounding_box()))
+}
+
+fn hit_all<'a>(objects: &'a [Hittable], r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+    let mut hit_anything = false;
+    let mut closest_so_far = ray_t.max;
+    for object in objects {
+        let mut temp_rec = HitRecord::default();
+        let temp_ray_t = Interval::new(ray_t.min, &closest_so_far);
+        if object.hit(r, &temp_ray_t, &mut temp_rec) {
+            hit_anything = true;
+            closest_so_far = temp_rec.t;
+            *rec = temp_rec;
         }
-        hit_anything
+    }
+    hit_anything
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color,
+        convert::to_f64,
+        material::{Lambertain, Mat},
+        sphere::Sphere,
+        texture::Texture,
+        vec3::{Point3, Vec3},
+    };
+
+    /// Unit sphere `distance` units down -z
+    fn sphere_at(distance: f64) -> Sphere {
+        let mat = Mat::Lambertain(Lambertain {
+            albedo: Texture::Solid(Color::new(0.5, 0.5, 0.5)),
+        });
+        Sphere::new(Point3::new(0.0, 0.0, -distance), 1.0, mat)
+    }
+
+    /// Distance to the first hit looking down -z from the origin
+    fn closest_hit(world: &impl Hit) -> Option<f64> {
+        let r = Ray::new(&Point3::default(), &Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        world.hit(&r, &Interval::new(0.001, &f64::INFINITY), &mut rec).then_some(rec.t)
+    }
+
+    #[test]
+    fn grows_past_any_fixed_size() {
+        let mut world = HittableList::new();
+        assert!(world.is_empty());
+        for k in 0..1000 {
+            world.add(sphere_at(to_f64(k).mul_add(3.0, 5.0)));
+        }
+        assert_eq!(world.len(), 1000);
+        assert_eq!(closest_hit(&world), Some(4.0));
+    }
+
+    #[test]
+    fn hit_is_closest_regardless_of_order() {
+        let world: HittableList = [sphere_at(20.0), sphere_at(5.0), sphere_at(10.0)].into_iter().collect();
+        assert_eq!(closest_hit(&world), Some(4.0));
+    }
+
+    #[test]
+    fn remove_and_clear() {
+        let mut world = HittableList::with_capacity(3);
+        world.extend([sphere_at(5.0), sphere_at(10.0)]);
+        world.add(sphere_at(20.0));
+        world.remove(0);
+        assert_eq!(world.len(), 2);
+        assert_eq!(closest_hit(&world), Some(9.0));
+        world.clear();
+        assert!(world.is_empty());
+        assert_eq!(closest_hit(&world), None);
+        assert!(world.bounding_box().is_empty());
+    }
+
+    #[test]
+    fn bounding_box_surrounds_every_object() {
+        let world: HittableList = [sphere_at(5.0), sphere_at(10.0)].into_iter().collect();
+        let bbox = world.bounding_box();
+        assert_eq!([bbox.z.min, bbox.z.max], [-11.0, -4.0]);
+        assert_eq!([bbox.x.min, bbox.x.max], [-1.0, 1.0]);
+    }
+
+    #[test]
+    fn converts_from_fixed_list() {
+        let mut fixed = FixedHittableList::<4>::new();
+        fixed.add(sphere_at(10.0));
+        fixed.add(sphere_at(5.0));
+        assert_eq!(fixed.objects().len(), 2);
+        let world = HittableList::from(&fixed);
+        assert_eq!(world.len(), 2);
+        assert_eq!(closest_hit(&world), closest_hit(&fixed));
     }
 }
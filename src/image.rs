@@ -0,0 +1,157 @@
+use crate::{color::Color, convert::floor_to_i64};
+
+/// How lookups outside an image are mapped back onto it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    /// Maps the pixel index `i` into `0..n`
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub const fn apply(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            Self::Repeat => i.rem_euclid(n),
+            Self::Clamp => if i < 0 { 0 } else if i >= n { n - 1 } else { i },
+            Self::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n { i } else { 2 * n - 1 - i }
+            }
+        };
+        i as usize
+    }
+}
+
+/// Grid of linear color values, stored row by row from the top left, along with any
+/// named single channel AOVs (arbitrary output values) laid out the same way
+#[derive(Clone, Debug, Default)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    aovs: Vec<(String, Vec<f64>)>,
+}
+
+impl Image {
+    /// Black image of the given size
+    #[must_use]
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); width * height],
+            aovs: Vec::new(),
+        }
+    }
+
+    /// Wraps existing pixels, returns `None` if there are not exactly `width * height` of them
+    #[must_use]
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Option<Self> {
+        (pixels.len() == width * height).then_some(Self {
+            width,
+            height,
+            pixels,
+            aovs: Vec::new(),
+        })
+    }
+
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    #[must_use]
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    #[must_use]
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the pixel is outside the image
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize) -> &Color {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        &self.pixels[y * self.width + x]
+    }
+
+    /// Pixel containing the continuous position `(x, y)`, where pixel `(i, j)` covers
+    /// `i..i + 1` by `j..j + 1`. Black for an empty image
+    #[must_use]
+    pub fn nearest(&self, x: f64, y: f64, wrap_x: WrapMode, wrap_y: WrapMode) -> Color {
+        if self.pixels.is_empty() {
+            return Color::default();
+        }
+        let i = wrap_x.apply(floor_to_i64(x), self.width);
+        let j = wrap_y.apply(floor_to_i64(y), self.height);
+        self.pixels[j * self.width + i]
+    }
+
+    /// Blend of the four pixels nearest to the continuous position `(x, y)`,
+    /// see `nearest`. Black for an empty image
+    #[must_use]
+    pub fn bilinear(&self, x: f64, y: f64, wrap_x: WrapMode, wrap_y: WrapMode) -> Color {
+        if self.pixels.is_empty() {
+            return Color::default();
+        }
+        // Shift so pixel centers land on whole numbers
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (floor_to_i64(x0), floor_to_i64(y0));
+        let pixel = |i: i64, j: i64| {
+            self.pixels[wrap_y.apply(j, self.height) * self.width + wrap_x.apply(i, self.width)]
+        };
+
+        let top = (1.0 - tx) * pixel(x0, y0) + tx * pixel(x0 + 1, y0);
+        let bottom = (1.0 - tx) * pixel(x0, y0 + 1) + tx * pixel(x0 + 1, y0 + 1);
+        (1.0 - ty) * top + ty * bottom
+    }
+
+    /// Values of the AOV called `name`, one per pixel
+    #[must_use]
+    pub fn aov(&self, name: &str) -> Option<&[f64]> {
+        self.aovs.iter().find(|(n, _)| n == name).map(|(_, values)| values.as_slice())
+    }
+
+    /// All AOVs as `(name, values)`, in the order they were added
+    pub fn aovs(&self) -> impl Iterator<Item = (&str, &[f64])> {
+        self.aovs.iter().map(|(name, values)| (name.as_str(), values.as_slice()))
+    }
+
+    /// Adds an AOV, replacing any existing one with the same name
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is not exactly one value per pixel
+    pub fn set_aov(&mut self, name: impl Into<String>, values: Vec<f64>) {
+        assert_eq!(values.len(), self.pixels.len(), "AOV size does not match the image");
+        let name = name.into();
+        if let Some(aov) = self.aovs.iter_mut().find(|(n, _)| *n == name) {
+            aov.1 = values;
+        } else {
+            self.aovs.push((name, values));
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the pixel is outside the image
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        self.pixels[y * self.width + x] = color;
+    }
+}
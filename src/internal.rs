@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
@@ -18,6 +19,29 @@ impl Interval {
         Self { min, max: *max }
     }
 
+    /// Smallest interval enclosing both `a` and `b`
+    #[must_use]
+    pub const fn enclosing(a: &Self, b: &Self) -> Self {
+        Self {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    #[must_use]
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    #[must_use]
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.0;
+        Self {
+            min: self.min - padding,
+            max: self.max + padding,
+        }
+    }
+
     #[must_use]
     pub fn clamp(&self, x: f64) -> f64 {
         if x < self.min {
@@ -1,24 +1,56 @@
+use rand::Rng;
+use rand_pcg::Pcg64Mcg;
+
+pub mod aabb;
+pub mod background;
+pub mod bvh;
 pub mod camera;
+pub mod cancel;
 pub mod color;
+mod convert;
+pub mod exr;
+pub mod film;
+pub mod filter;
+pub mod hdr;
 pub mod hittable;
 pub mod hittable_list;
+pub mod image;
 pub mod internal;
 pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod output;
+pub mod perlin;
+pub mod progress;
+pub mod png;
+pub mod quad;
 pub mod ray;
+pub mod sampler;
 pub mod sphere;
+pub mod texture;
+pub mod tile;
+pub mod tonemap;
+pub mod triangle;
 pub mod vec3;
+pub mod zlib;
 
 #[must_use]
 pub const fn degrees_to_radains(degrees: f64) -> f64 {
     degrees.to_radians()
 }
 
+/// Random number generator handed to everything that samples while rendering,
+/// seeded per pixel and sample so renders are reproducible
+///
+/// Unlike `SmallRng`, its output for a seed is the same on every platform and rand release
+pub type RenderRng = Pcg64Mcg;
+
 #[must_use]
-pub fn rand_f64() -> f64 {
-    rand::random_range(0.0..=1.0)
+pub fn rand_f64<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    rng.random_range(0.0..=1.0)
 }
 
 #[must_use]
-pub fn rand_range_f64(min: f64, max: f64) -> f64 {
-    rand::random_range(min..=max)
+pub fn rand_range_f64<R: Rng + ?Sized>(rng: &mut R, min: f64, max: f64) -> f64 {
+    rng.random_range(min..=max)
 }
@@ -1,53 +1,224 @@
+use std::{f64::consts::PI, sync::Arc};
+
 use crate::{
     color::Color,
     hittable::HitRecord,
-    rand_f64,
     ray::Ray,
+    sampler::Sampler,
+    texture::Texture,
     vec3::{
         dot,
-        random_unit_vector,
         reflect,
         refract,
+        sample_unit_vector,
         unit_vector,
     },
 };
 
-#[derive(Clone, Copy)]
+/// How light interacts with a surface, implement this to add new materials
+pub trait Scatter: Send + Sync {
+    /// Sets the ray leaving the surface and its color attenuation, drawing any random
+    /// numbers from `sampler`. Returns false if the incoming ray is absorbed
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> bool;
+
+    /// Light given off by the surface at the hit, black for anything but light sources
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::default()
+    }
+
+    /// Density over solid angle of `scatter` sending the ray along `scattered`.
+    /// Zero, the default, marks scattering that cannot be combined with light
+    /// sampling, such as mirrors and glass
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+}
+
+/// Built in materials, plus `Custom` for anything implementing `Scatter`
+#[derive(Clone)]
 pub enum Mat {
-    Metal { albedo: Color, fuzz: f64 },
-    Lambertain { albedo: Color },
-    Dielectric { refraction_index: f64 },
+    Metal(Metal),
+    Lambertain(Lambertain),
+    Dielectric(Dielectric),
+    DiffuseLight(DiffuseLight),
+    Custom(Arc<dyn Scatter>),
 }
 
 impl Mat {
-    pub fn scatter(
+    /// Wraps a user defined material
+    pub fn custom(mat: impl Scatter + 'static) -> Self {
+        Self::Custom(Arc::new(mat))
+    }
+}
+
+impl Scatter for Mat {
+    fn scatter(
         &self,
         r_in: &Ray,
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut Sampler,
     ) -> bool {
         match self {
-            Self::Lambertain { albedo } => {
-                lambertain_scatter(albedo, r_in, rec, attenuation, scattered)
-            }
-            Self::Metal { albedo, fuzz } => {
-                metal_scatter(albedo, *fuzz, r_in, rec, attenuation, scattered)
-            }
-            Self::Dielectric { refraction_index } => {
-                dielectric_scatter(*refraction_index, r_in, rec, attenuation, scattered)
-            }
+            Self::Lambertain(mat) => mat.scatter(r_in, rec, attenuation, scattered, sampler),
+            Self::Metal(mat) => mat.scatter(r_in, rec, attenuation, scattered, sampler),
+            Self::Dielectric(mat) => mat.scatter(r_in, rec, attenuation, scattered, sampler),
+            Self::DiffuseLight(mat) => mat.scatter(r_in, rec, attenuation, scattered, sampler),
+            Self::Custom(mat) => mat.scatter(r_in, rec, attenuation, scattered, sampler),
+        }
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        match self {
+            Self::DiffuseLight(mat) => mat.emitted(r_in, rec),
+            Self::Custom(mat) => mat.emitted(r_in, rec),
+            Self::Lambertain(_) | Self::Metal(_) | Self::Dielectric(_) => Color::default(),
+        }
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        match self {
+            Self::Lambertain(mat) => mat.scattering_pdf(r_in, rec, scattered),
+            Self::Custom(mat) => mat.scattering_pdf(r_in, rec, scattered),
+            Self::Metal(_) | Self::Dielectric(_) | Self::DiffuseLight(_) => 0.0,
+        }
+    }
+}
+
+impl From<Lambertain> for Mat {
+    fn from(mat: Lambertain) -> Self {
+        Self::Lambertain(mat)
+    }
+}
+
+impl From<Metal> for Mat {
+    fn from(mat: Metal) -> Self {
+        Self::Metal(mat)
+    }
+}
+
+impl From<Dielectric> for Mat {
+    fn from(mat: Dielectric) -> Self {
+        Self::Dielectric(mat)
+    }
+}
+
+impl From<DiffuseLight> for Mat {
+    fn from(mat: DiffuseLight) -> Self {
+        Self::DiffuseLight(mat)
+    }
+}
+
+/// Diffuse surface
+#[derive(Clone)]
+pub struct Lambertain {
+    pub albedo: Texture,
+}
+
+impl Scatter for Lambertain {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> bool {
+        let albedo = self.albedo.value(rec.u, rec.v, &rec.p);
+        lambertain_scatter(&albedo, r_in, rec, attenuation, scattered, sampler)
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = dot(&rec.normal, &unit_vector(scattered.direction()));
+        cos_theta.max(0.0) / PI
+    }
+}
+
+/// Reflective surface, `fuzz` blurs the reflection
+#[derive(Clone)]
+pub struct Metal {
+    pub albedo: Texture,
+    pub fuzz: f64,
+}
+
+impl Scatter for Metal {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> bool {
+        let albedo = self.albedo.value(rec.u, rec.v, &rec.p);
+        metal_scatter(&albedo, self.fuzz, r_in, rec, attenuation, scattered, sampler)
+    }
+}
+
+/// Clear refractive material such as glass or water
+#[derive(Clone, Copy)]
+pub struct Dielectric {
+    pub refraction_index: f64,
+}
+
+impl Scatter for Dielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> bool {
+        dielectric_scatter(self.refraction_index, r_in, rec, attenuation, scattered, sampler)
+    }
+}
+
+/// Light source emitting `emit` radiance from its front face, or from both faces if `two_sided`
+#[derive(Clone, Copy)]
+pub struct DiffuseLight {
+    pub emit: Color,
+    pub two_sided: bool,
+}
+
+impl Scatter for DiffuseLight {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _attenuation: &mut Color,
+        _scattered: &mut Ray,
+        _sampler: &mut Sampler,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        if rec.front_face || self.two_sided {
+            self.emit
+        } else {
+            Color::default()
         }
     }
 }
+
 fn lambertain_scatter(
     albedo: &Color,
     _r_in: &Ray,
     rec: &HitRecord,
     attenuation: &mut Color,
     scattered: &mut Ray,
+    sampler: &mut Sampler,
 ) -> bool {
-    let mut scatter_direction = &(rec.normal + random_unit_vector());
+    let mut scatter_direction = &(rec.normal + sample_unit_vector(sampler.next_2d()));
 
     if scatter_direction.near_zero() {
         scatter_direction = &rec.normal;
@@ -65,9 +236,10 @@ fn metal_scatter(
     rec: &HitRecord,
     attenuation: &mut Color,
     scattered: &mut Ray,
+    sampler: &mut Sampler,
 ) -> bool {
     let reflected = reflect(&r_in.dir, &rec.normal);
-    let reflected = unit_vector(&reflected) + (fuzz * random_unit_vector());
+    let reflected = unit_vector(&reflected) + (fuzz * sample_unit_vector(sampler.next_2d()));
     scattered.change(&rec.p, &reflected);
     attenuation.change(albedo.x(), albedo.y(), albedo.z());
 
@@ -80,6 +252,7 @@ fn dielectric_scatter(
     rec: &HitRecord,
     attenuation: &mut Color,
     scattered: &mut Ray,
+    sampler: &mut Sampler,
 ) -> bool {
     attenuation.change(1.0, 1.0, 1.0);
     let ri = if rec.front_face {
@@ -90,10 +263,10 @@ fn dielectric_scatter(
     let unit_direction = unit_vector(r_in.direction());
 
     let cos_theta = dot(&-&unit_direction, &rec.normal).min(1.0);
-    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let sin_theta = cos_theta.mul_add(-cos_theta, 1.0).sqrt();
 
     let cannot_refract = ri * sin_theta > 1.0;
-    let direction = if cannot_refract || reflectance(cos_theta, ri) > rand_f64() {
+    let direction = if cannot_refract || reflectance(cos_theta, ri) > sampler.next_1d() {
         reflect(&unit_direction, &rec.normal)
     } else {
         refract(&unit_direction, &rec.normal, &ri)
@@ -109,3 +282,135 @@ fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
     let r0 = r0 * r0;
     (1.0 - r0).mul_add((1.0 - cosine).powi(5), r0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sampler::SamplerKind,
+        vec3::{Point3, Vec3},
+    };
+
+    /// Reflects straight back along the normal, tinted, and glows faintly
+    struct Retroreflector;
+
+    impl Scatter for Retroreflector {
+        fn scatter(
+            &self,
+            _r_in: &Ray,
+            rec: &HitRecord,
+            attenuation: &mut Color,
+            scattered: &mut Ray,
+            _sampler: &mut Sampler,
+        ) -> bool {
+            attenuation.change(0.25, 0.5, 0.75);
+            scattered.change(&rec.p, &rec.normal);
+            true
+        }
+
+        fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+            Color::new(0.125, 0.125, 0.125)
+        }
+
+        fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+            2.0
+        }
+    }
+
+    /// Absorbs everything and leaves the other methods at their defaults
+    struct Absorber;
+
+    impl Scatter for Absorber {
+        fn scatter(
+            &self,
+            _r_in: &Ray,
+            _rec: &HitRecord,
+            _attenuation: &mut Color,
+            _scattered: &mut Ray,
+            _sampler: &mut Sampler,
+        ) -> bool {
+            false
+        }
+    }
+
+    /// Hit at the origin of a surface facing +y, by a ray coming down onto it
+    fn hit_from_above() -> (Ray, HitRecord<'static>) {
+        let r = Ray::new(&Point3::new(1.0, 1.0, 0.0), &Vec3::new(-1.0, -1.0, 0.0));
+        (r, HitRecord::new(Point3::default(), Vec3::new(0.0, 1.0, 0.0), 1.0))
+    }
+
+    fn sampler() -> Sampler {
+        Sampler::new(SamplerKind::Independent, 0, [0, 0], 0, 1)
+    }
+
+    #[test]
+    fn custom_material_is_dispatched_through_the_enum() {
+        let mat = Mat::custom(Retroreflector);
+        let (r, rec) = hit_from_above();
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::new(&Point3::default(), &Vec3::default());
+        assert!(mat.scatter(&r, &rec, &mut attenuation, &mut scattered, &mut sampler()));
+        assert_eq!(attenuation.e, [0.25, 0.5, 0.75]);
+        assert_eq!(scattered.direction().e, [0.0, 1.0, 0.0]);
+        assert_eq!(mat.emitted(&r, &rec).e, [0.125; 3]);
+        assert!((mat.scattering_pdf(&r, &rec, &scattered) - 2.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn custom_material_defaults_to_no_light_and_no_pdf() {
+        let mat = Mat::custom(Absorber);
+        let (r, rec) = hit_from_above();
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::new(&Point3::default(), &Vec3::default());
+        assert!(!mat.scatter(&r, &rec, &mut attenuation, &mut scattered, &mut sampler()));
+        assert_eq!(mat.emitted(&r, &rec).e, [0.0; 3]);
+        assert!(mat.scattering_pdf(&r, &rec, &scattered).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn diffuse_light_emits_from_front_face() {
+        let light = Mat::DiffuseLight(DiffuseLight {
+            emit: Color::new(4.0, 2.0, 1.0),
+            two_sided: false,
+        });
+        let (r, mut rec) = hit_from_above();
+        assert_eq!(light.emitted(&r, &rec).e, [4.0, 2.0, 1.0]);
+        rec.front_face = false;
+        assert_eq!(light.emitted(&r, &rec).e, [0.0; 3]);
+    }
+
+    #[test]
+    fn two_sided_light_emits_from_both_faces() {
+        let light = Mat::DiffuseLight(DiffuseLight {
+            emit: Color::new(4.0, 2.0, 1.0),
+            two_sided: true,
+        });
+        let (r, mut rec) = hit_from_above();
+        rec.front_face = false;
+        assert_eq!(light.emitted(&r, &rec).e, [4.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn lights_absorb_and_other_materials_do_not_emit() {
+        let (r, rec) = hit_from_above();
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::new(&Point3::default(), &Vec3::default());
+        let light = Mat::from(DiffuseLight {
+            emit: Color::new(1.0, 1.0, 1.0),
+            two_sided: false,
+        });
+        assert!(!light.scatter(&r, &rec, &mut attenuation, &mut scattered, &mut sampler()));
+        assert!(light.scattering_pdf(&r, &rec, &scattered).abs() < f64::EPSILON);
+        let diffuse = Mat::from(Lambertain {
+            albedo: Texture::Solid(Color::new(0.5, 0.5, 0.5)),
+        });
+        let metal = Mat::from(Metal {
+            albedo: Texture::Solid(Color::new(0.5, 0.5, 0.5)),
+            fuzz: 0.0,
+        });
+        let glass = Mat::from(Dielectric { refraction_index: 1.5 });
+        for mat in [diffuse, metal, glass] {
+            assert_eq!(mat.emitted(&r, &rec).e, [0.0; 3]);
+        }
+    }
+}
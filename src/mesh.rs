@@ -0,0 +1,267 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::Bvh,
+    hittable::{Hit, HitRecord},
+    internal::Interval,
+    material::Mat,
+    ray::Ray,
+    triangle::{geometric_normal, intersect, triangle_bounds},
+    vec3::{Point3, Vec3, dot, unit_vector},
+};
+
+/// Vertex buffers shared by every triangle of a mesh
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Option<Vec<Vec3>>,   // Per vertex shading normals
+    pub uvs: Option<Vec<(f64, f64)>>, // Per vertex texture coordinates
+    pub indices: Vec<[usize; 3]>,     // Vertex indices of each triangle
+    pub mat: Mat,
+}
+
+/// Indexed triangle mesh, traced through its own bounding volume hierarchy
+/// so the whole mesh is a single object in the world
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+    bvh: Bvh<MeshTriangle>,
+}
+
+impl TriangleMesh {
+    /// Builds the mesh and its hierarchy
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index is out of range of `positions`, or if `normals` or `uvs`
+    /// do not have one entry per position
+    #[must_use]
+    pub fn new(data: MeshData) -> Self {
+        let vertex_count = data.positions.len();
+        assert!(
+            data.indices.iter().flatten().all(|&i| i < vertex_count),
+            "mesh index out of range"
+        );
+        assert!(
+            data.normals.as_ref().is_none_or(|n| n.len() == vertex_count),
+            "mesh needs one normal per position"
+        );
+        assert!(
+            data.uvs.as_ref().is_none_or(|uv| uv.len() == vertex_count),
+            "mesh needs one uv per position"
+        );
+
+        let data = Arc::new(data);
+        let triangles = (0..data.indices.len())
+            .map(|index| MeshTriangle {
+                mesh: Arc::clone(&data),
+                index,
+            })
+            .collect();
+        Self {
+            bvh: Bvh::new(triangles),
+            data,
+        }
+    }
+
+    #[must_use]
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    #[must_use]
+    pub fn triangle_count(&self) -> usize {
+        self.data.indices.len()
+    }
+}
+
+impl Hit for TriangleMesh {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        self.bvh.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+}
+
+impl Hit for MeshTriangle {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        let mesh = &*self.mesh;
+        let [i0, i1, i2] = mesh.indices[self.index];
+        let (p0, p1, p2) = (&mesh.positions[i0], &mesh.positions[i1], &mesh.positions[i2]);
+        let Some((t, b1, b2)) = intersect(r, ray_t, p0, p1, p2) else {
+            return false;
+        };
+        let b0 = 1.0 - b1 - b2;
+
+        rec.t = t;
+        rec.p = r.at(t);
+        (rec.u, rec.v) = mesh.uvs.as_ref().map_or((b1, b2), |uvs| {
+            let (uv0, uv1, uv2) = (uvs[i0], uvs[i1], uvs[i2]);
+            (
+                b2.mul_add(uv2.0, b0.mul_add(uv0.0, b1 * uv1.0)),
+                b2.mul_add(uv2.1, b0.mul_add(uv0.1, b1 * uv1.1)),
+            )
+        });
+        rec.mat = &mesh.mat;
+
+        let geometric = geometric_normal(p0, p1, p2);
+        let Some(normals) = &mesh.normals else {
+            rec.set_face_normal(r, geometric);
+            return true;
+        };
+        let shading = unit_vector(&(b0 * normals[i0] + b1 * normals[i1] + b2 * normals[i2]));
+        // Take the side of the surface from the geometry, oriented to agree with the shading normal
+        let geometric = if dot(&geometric, &shading) < 0.0 {
+            -geometric
+        } else {
+            geometric
+        };
+        rec.set_face_normal(r, geometric);
+        rec.normal = if rec.front_face { shading } else { -shading };
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [i0, i1, i2] = self.mesh.indices[self.index];
+        let positions = &self.mesh.positions;
+        triangle_bounds(&positions[i0], &positions[i1], &positions[i2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::{RenderRng, color::Color, material::Lambertain, texture::Texture};
+
+    fn grey() -> Mat {
+        Mat::Lambertain(Lambertain {
+            albedo: Texture::Solid(Color::new(0.5, 0.5, 0.5)),
+        })
+    }
+
+    /// Triangle in the z = 0 plane, wound counterclockwise seen from +z
+    fn single_triangle(normals: Option<Vec<Vec3>>) -> TriangleMesh {
+        TriangleMesh::new(MeshData {
+            positions: vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)],
+            normals,
+            uvs: None,
+            indices: vec![[0, 1, 2]],
+            mat: grey(),
+        })
+    }
+
+    fn hit<'a>(mesh: &'a TriangleMesh, origin: &Point3, direction: &Vec3) -> Option<HitRecord<'a>> {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(origin, direction);
+        mesh.hit(&r, &Interval::new(0.001, &f64::INFINITY), &mut rec).then_some(rec)
+    }
+
+    fn assert_close(actual: &Vec3, expected: &Vec3) {
+        assert!((*actual - *expected).len() < 1e-12, "{:?} is not {:?}", actual.e, expected.e);
+    }
+
+    #[test]
+    fn smooth_normals_match_vertex_normals() {
+        let normals = vec![
+            unit_vector(&Vec3::new(-1.0, -1.0, 2.0)),
+            unit_vector(&Vec3::new(1.0, 0.0, 2.0)),
+            unit_vector(&Vec3::new(0.0, 1.0, 2.0)),
+        ];
+        let mesh = single_triangle(Some(normals.clone()));
+        // Straight down onto a vertex grazes the bounding box, which the hierarchy counts as a miss
+        let triangle = MeshTriangle {
+            mesh: Arc::clone(&mesh.data),
+            index: 0,
+        };
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        for (vertex, normal) in mesh.data().positions.iter().zip(&normals) {
+            let mut rec = HitRecord::default();
+            let r = Ray::new(&(vertex - down), &down);
+            assert!(triangle.hit(&r, &Interval::new(0.001, &f64::INFINITY), &mut rec), "missed {:?}", vertex.e);
+            assert!(rec.front_face);
+            assert_close(&rec.normal, normal);
+        }
+        let center = hit(&mesh, &Point3::new(1.0 / 3.0, 1.0 / 3.0, 1.0), &down).expect("ray hits the middle");
+        assert_close(&center.normal, &unit_vector(&(normals[0] + normals[1] + normals[2])));
+    }
+
+    #[test]
+    fn smooth_normals_face_the_ray() {
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let mesh = single_triangle(Some(vec![up; 3]));
+        let back = hit(&mesh, &Point3::new(0.25, 0.25, -1.0), &up).expect("hit from the back");
+        assert!(!back.front_face);
+        assert_close(&back.normal, &-up);
+    }
+
+    #[test]
+    fn smooth_normals_decide_front_face_against_winding() {
+        // Wound clockwise seen from +z, but the vertex normals say the surface faces +z
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let mesh = TriangleMesh::new(MeshData {
+            positions: vec![Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), Point3::new(1.0, 0.0, 0.0)],
+            normals: Some(vec![up; 3]),
+            uvs: None,
+            indices: vec![[0, 1, 2]],
+            mat: grey(),
+        });
+        let front = hit(&mesh, &Point3::new(0.25, 0.25, 1.0), &-up).expect("hit from above");
+        assert!(front.front_face);
+        assert_close(&front.normal, &up);
+    }
+
+    #[test]
+    fn flat_mesh_uses_geometric_normal() {
+        let mesh = single_triangle(None);
+        let rec = hit(&mesh, &Point3::new(0.2, 0.3, 1.0), &Vec3::new(0.0, 0.0, -1.0)).expect("ray hits the triangle");
+        assert!(rec.front_face);
+        assert_close(&rec.normal, &Vec3::new(0.0, 0.0, 1.0));
+        assert!((rec.u - 0.2).abs() < 1e-12 && (rec.v - 0.3).abs() < 1e-12);
+    }
+
+    #[test]
+    fn hits_match_brute_force() {
+        let mut rng = RenderRng::seed_from_u64(5);
+        let point = |rng: &mut RenderRng| {
+            Point3::new(rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0))
+        };
+        let positions: Vec<Point3> = (0..150).map(|_| point(&mut rng)).collect();
+        let indices: Vec<[usize; 3]> = (0..50).map(|k| [3 * k, 3 * k + 1, 3 * k + 2]).collect();
+        let mesh = TriangleMesh::new(MeshData {
+            positions: positions.clone(),
+            normals: None,
+            uvs: None,
+            indices: indices.clone(),
+            mat: grey(),
+        });
+        let ray_t = Interval::new(0.001, &f64::INFINITY);
+        let mut hits = 0;
+        for _ in 0..500 {
+            // From outside the mesh towards a point among its triangles
+            let origin = 3.0 * point(&mut rng);
+            let r = Ray::new(&origin, &(point(&mut rng) - origin));
+            let closest = indices
+                .iter()
+                .filter_map(|&[i0, i1, i2]| intersect(&r, &ray_t, &positions[i0], &positions[i1], &positions[i2]))
+                .map(|(t, _, _)| t)
+                .min_by(f64::total_cmp);
+            let mut rec = HitRecord::default();
+            let found = mesh.hit(&r, &ray_t, &mut rec).then_some(rec.t);
+            match (found, closest) {
+                (Some(found), Some(closest)) => assert!((found - closest).abs() < 1e-12, "{found} instead of {closest}"),
+                (None, None) => {}
+                _ => panic!("mesh gives {found:?}, brute force {closest:?}"),
+            }
+            hits += usize::from(closest.is_some());
+        }
+        assert!(hits > 50, "only {hits} rays hit the mesh");
+    }
+}
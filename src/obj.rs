@@ -0,0 +1,504 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    color::Color,
+    material::{Dielectric, Lambertain, Mat, Metal},
+    mesh::{MeshData, TriangleMesh},
+    vec3::{Point3, Vec3},
+};
+
+/// Albedo of faces without a material, and of materials without a `Kd`
+const DEFAULT_ALBEDO: Color = Color::new(0.8, 0.8, 0.8);
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { .. } => None,
+        }
+    }
+}
+
+/// Loads a Wavefront OBJ file and the MTL libraries it references.
+/// Returns one mesh per group and material, polygons are fan triangulated
+///
+/// # Errors
+///
+/// This function will return an error if a file cannot be read, if a line of the OBJ or MTL
+/// files is malformed, if a face refers to a vertex that is not defined, or if `usemtl` names
+/// a material that no earlier `mtllib` defines
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Vec<TriangleMesh>, ObjError> {
+    let path = path.as_ref();
+    let mut parser = ObjParser::default();
+    for_each_line(path, |line, keyword, args| parser.parse_line(path, line, keyword, args))?;
+    Ok(parser.finish())
+}
+
+/// Loads the materials of a Wavefront MTL file by name.
+///
+/// `Kd` makes a `Lambertain` surface. A `d` (or `Tr`) below full opacity makes a
+/// `Dielectric` with the `Ni` index of refraction. A `Ks` brighter than `Kd` makes a
+/// `Metal`, with `Ns` mapped to fuzz
+///
+/// # Errors
+///
+/// This function will return an error if the file cannot be read or a line is malformed
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<HashMap<String, Mat>, ObjError> {
+    let path = path.as_ref();
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlParams)> = None;
+    for_each_line(path, |line, keyword, args| {
+        let error = |message: String| parse_error(path, line, message);
+        if keyword == "newmtl" {
+            if let Some((name, params)) = current.take() {
+                materials.insert(name, params.to_mat());
+            }
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(error("newmtl needs a name".to_string()));
+            }
+            current = Some((name, MtlParams::default()));
+            return Ok(());
+        }
+        let Some((_, params)) = current.as_mut() else {
+            return Err(error(format!("'{keyword}' before any newmtl")));
+        };
+        match keyword {
+            "Kd" => params.kd = Some(parse_color(args).map_err(error)?),
+            "Ks" => params.ks = Some(parse_color(args).map_err(error)?),
+            "Ns" => params.ns = Some(parse_floats::<1>(args).map_err(error)?[0]),
+            "Ni" => params.ni = Some(parse_floats::<1>(args).map_err(error)?[0]),
+            "d" => params.d = Some(parse_floats::<1>(args).map_err(error)?[0]),
+            "Tr" => params.d = Some(1.0 - parse_floats::<1>(args).map_err(error)?[0]),
+            // Texture maps, illumination models and the like have no equivalent
+            _ => {}
+        }
+        Ok(())
+    })?;
+    if let Some((name, params)) = current {
+        materials.insert(name, params.to_mat());
+    }
+    Ok(materials)
+}
+
+#[derive(Default)]
+struct MtlParams {
+    kd: Option<Color>,
+    ks: Option<Color>,
+    ns: Option<f64>,
+    ni: Option<f64>,
+    d: Option<f64>,
+}
+
+impl MtlParams {
+    fn to_mat(&self) -> Mat {
+        if self.d.is_some_and(|d| d < 1.0) {
+            return Mat::Dielectric(Dielectric {
+                refraction_index: self.ni.unwrap_or(1.5),
+            });
+        }
+        let kd = self.kd.unwrap_or(DEFAULT_ALBEDO);
+        if let Some(ks) = self.ks
+            && max_component(&ks) > max_component(&kd)
+        {
+            // Phong exponent to roughness, the usual sqrt(2 / (Ns + 2)) approximation
+            let ns = self.ns.unwrap_or(0.0).max(0.0);
+            let fuzz = (2.0 / (ns + 2.0)).sqrt().clamp(0.0, 1.0);
+            return Mat::Metal(Metal {
+                albedo: ks.into(),
+                fuzz,
+            });
+        }
+        Mat::Lambertain(Lambertain { albedo: kd.into() })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FaceVertex {
+    v: usize,
+    vt: Option<usize>,
+    vn: Option<usize>,
+}
+
+struct Group {
+    material: Option<String>,
+    triangles: Vec<[FaceVertex; 3]>,
+}
+
+#[derive(Default)]
+struct ObjParser {
+    positions: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+    materials: HashMap<String, Mat>,
+    groups: Vec<Group>,
+    group_lookup: HashMap<(String, Option<String>), usize>,
+    group_name: String,
+    material: Option<String>,
+    current: Option<usize>, // Group faces are currently added to
+}
+
+impl ObjParser {
+    fn parse_line(&mut self, path: &Path, line: usize, keyword: &str, args: &[&str]) -> Result<(), ObjError> {
+        let error = |message: String| parse_error(path, line, message);
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(args).map_err(error)?;
+                self.positions.push(Point3::new(x, y, z));
+            }
+            "vt" => {
+                let u = parse_floats::<1>(args).map_err(error)?[0];
+                let v = args.get(1).map_or(Ok(0.0), |s| parse_float(s)).map_err(error)?;
+                self.uvs.push((u, v));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(args).map_err(error)?;
+                self.normals.push(Vec3::new(x, y, z));
+            }
+            "f" => {
+                let face = args
+                    .iter()
+                    .map(|s| self.parse_face_vertex(s))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                if face.len() < 3 {
+                    return Err(error(format!("face needs at least 3 vertices, found {}", face.len())));
+                }
+                let group = self.current_group();
+                for i in 1..face.len() - 1 {
+                    self.groups[group].triangles.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                self.group_name = args.join(" ");
+                self.current = None;
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if !self.materials.contains_key(&name) {
+                    return Err(error(format!("unknown material '{name}'")));
+                }
+                self.material = Some(name);
+                self.current = None;
+            }
+            "mtllib" => {
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                for file in args {
+                    self.materials.extend(load_mtl(dir.join(file))?);
+                }
+            }
+            // Smoothing groups, lines, points and free form geometry are not supported
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving negative indices
+    fn parse_face_vertex(&self, s: &str) -> Result<FaceVertex, String> {
+        let mut parts = s.split('/');
+        let v = parts.next().unwrap_or_default();
+        let vt = parts.next().filter(|p| !p.is_empty());
+        let vn = parts.next().filter(|p| !p.is_empty());
+        if parts.next().is_some() {
+            return Err(format!("malformed face vertex '{s}'"));
+        }
+        Ok(FaceVertex {
+            v: resolve_index(v, self.positions.len(), "position")?,
+            vt: vt
+                .map(|vt| resolve_index(vt, self.uvs.len(), "texture coordinate"))
+                .transpose()?,
+            vn: vn
+                .map(|vn| resolve_index(vn, self.normals.len(), "normal"))
+                .transpose()?,
+        })
+    }
+
+    fn current_group(&mut self) -> usize {
+        if let Some(group) = self.current {
+            return group;
+        }
+        let key = (self.group_name.clone(), self.material.clone());
+        let next = self.groups.len();
+        let group = *self.group_lookup.entry(key).or_insert(next);
+        if group == next {
+            self.groups.push(Group {
+                material: self.material.clone(),
+                triangles: Vec::new(),
+            });
+        }
+        self.current = Some(group);
+        group
+    }
+
+    fn finish(self) -> Vec<TriangleMesh> {
+        self.groups
+            .iter()
+            .filter(|group| !group.triangles.is_empty())
+            .map(|group| TriangleMesh::new(self.mesh_data(group)))
+            .collect()
+    }
+
+    /// Builds the vertex buffers of a group, with one vertex per distinct attribute combination
+    fn mesh_data(&self, group: &Group) -> MeshData {
+        let corners = || group.triangles.iter().flatten();
+        let has_uvs = corners().all(|fv| fv.vt.is_some());
+        let has_normals = corners().all(|fv| fv.vn.is_some());
+
+        let mut lookup = HashMap::new();
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        let mut normals = Vec::new();
+        let indices = group
+            .triangles
+            .iter()
+            .map(|triangle| {
+                triangle.map(|fv| {
+                    let key = FaceVertex {
+                        v: fv.v,
+                        vt: fv.vt.filter(|_| has_uvs),
+                        vn: fv.vn.filter(|_| has_normals),
+                    };
+                    *lookup.entry(key).or_insert_with(|| {
+                        positions.push(self.positions[key.v]);
+                        if let Some(vt) = key.vt {
+                            uvs.push(self.uvs[vt]);
+                        }
+                        if let Some(vn) = key.vn {
+                            normals.push(self.normals[vn]);
+                        }
+                        positions.len() - 1
+                    })
+                })
+            })
+            .collect();
+
+        let mat = group
+            .material
+            .as_ref()
+            .and_then(|name| self.materials.get(name))
+            .cloned()
+            .unwrap_or_else(|| {
+                Mat::Lambertain(Lambertain {
+                    albedo: DEFAULT_ALBEDO.into(),
+                })
+            });
+        MeshData {
+            positions,
+            normals: has_normals.then_some(normals),
+            uvs: has_uvs.then_some(uvs),
+            indices,
+            mat,
+        }
+    }
+}
+
+/// Calls `f` with the line number, keyword and arguments of every non empty line
+fn for_each_line(
+    path: &Path,
+    mut f: impl FnMut(usize, &str, &[&str]) -> Result<(), ObjError>,
+) -> Result<(), ObjError> {
+    let io_error = |source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let reader = BufReader::new(File::open(path).map_err(io_error)?);
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        let content = line.split('#').next().unwrap_or_default();
+        let mut tokens = content.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+        f(i + 1, keyword, &args)?;
+    }
+    Ok(())
+}
+
+fn parse_error(path: &Path, line: usize, message: String) -> ObjError {
+    ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    }
+}
+
+/// Converts a one based, or negative relative, OBJ index into a zero based one
+fn resolve_index(s: &str, count: usize, what: &str) -> Result<usize, String> {
+    let index: i64 = s
+        .parse()
+        .map_err(|_| format!("expected {what} index, found '{s}'"))?;
+    let resolved = match index {
+        0 => None,
+        i if i > 0 => usize::try_from(i - 1).ok(),
+        i => usize::try_from(i.unsigned_abs())
+            .ok()
+            .and_then(|back| count.checked_sub(back)),
+    };
+    resolved
+        .filter(|&i| i < count)
+        .ok_or_else(|| format!("{what} index {index} out of range, {count} defined"))
+}
+
+fn parse_float(s: &str) -> Result<f64, String> {
+    s.parse()
+        .map_err(|_| format!("expected a number, found '{s}'"))
+}
+
+fn parse_floats<const N: usize>(args: &[&str]) -> Result<[f64; N], String> {
+    if args.len() < N {
+        return Err(format!("expected {N} numbers, found {}", args.len()));
+    }
+    let mut values = [0.0; N];
+    for (value, s) in values.iter_mut().zip(args) {
+        *value = parse_float(s)?;
+    }
+    Ok(values)
+}
+
+fn parse_color(args: &[&str]) -> Result<Color, String> {
+    // A single value is a grey
+    if args.len() == 1 {
+        let v = parse_float(args[0])?;
+        return Ok(Color::new(v, v, v));
+    }
+    let [r, g, b] = parse_floats::<3>(args)?;
+    Ok(Color::new(r, g, b))
+}
+
+fn max_component(c: &Color) -> f64 {
+    c.x().max(c.y()).max(c.z())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Writes `obj`, and `mtl` as `scene.mtl` next to it, to a temporary directory and loads them
+    fn load(name: &str, obj: &str, mtl: &str) -> Result<Vec<TriangleMesh>, ObjError> {
+        let dir = std::env::temp_dir().join(format!("rtiaw-obj-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("temporary directory should be writable");
+        fs::write(dir.join("scene.obj"), obj).expect("temporary file should be writable");
+        fs::write(dir.join("scene.mtl"), mtl).expect("temporary file should be writable");
+        let result = load_obj(dir.join("scene.obj"));
+        fs::remove_dir_all(&dir).ok();
+        result
+    }
+
+    fn positions(mesh: &TriangleMesh) -> Vec<[f64; 3]> {
+        let data = mesh.data();
+        data.indices.iter().flatten().map(|&i| data.positions[i].e).collect()
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn resolves_negative_indices() {
+        let obj = format!("{SQUARE}f -4 -3 -2\nv 2 2 2\nf 1 -1 -2\n");
+        let meshes = load("negative", &obj, "").expect("valid OBJ");
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(
+            positions(&meshes[0]),
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 0.0], [2.0, 2.0, 2.0], [0.0, 1.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn triangulates_polygons_as_fans() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n";
+        let meshes = load("fan", obj, "").expect("valid OBJ");
+        assert_eq!(meshes[0].data().indices, [[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+
+        let meshes = load("quad", &format!("{SQUARE}f 1 2 3 4\n"), "").expect("valid OBJ");
+        assert_eq!(meshes[0].data().indices, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn reads_normal_only_face_vertices() {
+        let obj = format!("{SQUARE}vn 0 0 1\nvn 0 0 -1\nf 1//1 2//1 3//2\n");
+        let meshes = load("normals", &obj, "").expect("valid OBJ");
+        let data = meshes[0].data();
+        assert!(data.uvs.is_none());
+        let normals = data.normals.as_ref().expect("every corner has a normal");
+        let z: Vec<f64> = data.indices[0].iter().map(|&i| normals[i].z()).collect();
+        assert_eq!(z, [1.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn reads_texture_only_face_vertices() {
+        let obj = format!("{SQUARE}vt 0 0\nvt 1 0\nvt 1 1\nf 1/1 2/2 3/3\n");
+        let meshes = load("uvs", &obj, "").expect("valid OBJ");
+        let data = meshes[0].data();
+        assert!(data.normals.is_none());
+        let uvs = data.uvs.as_ref().expect("every corner has texture coordinates");
+        let corners: Vec<(f64, f64)> = data.indices[0].iter().map(|&i| uvs[i]).collect();
+        assert_eq!(corners, [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+    }
+
+    #[test]
+    fn reports_out_of_range_index_with_line() {
+        let obj = format!("{SQUARE}f 1 2 3\n\nf 1 2 5\n");
+        let Err(ObjError::Parse { line, message, .. }) = load("range", &obj, "") else {
+            panic!("index 5 of 4 positions should be rejected");
+        };
+        assert_eq!(line, 7);
+        assert!(message.contains("out of range"), "{message}");
+
+        let Err(ObjError::Parse { line, .. }) = load("relative", &format!("{SQUARE}f -5 1 2\n"), "") else {
+            panic!("index -5 of 4 positions should be rejected");
+        };
+        assert_eq!(line, 5);
+    }
+
+    #[test]
+    fn uses_materials_from_library() {
+        let obj = format!("mtllib scene.mtl\n{SQUARE}f 1 2 3\nusemtl red\nf 1 3 4\n");
+        let meshes = load("material", &obj, "newmtl red\nKd 1 0 0\n").expect("valid OBJ");
+        assert_eq!(meshes.len(), 2);
+        let albedo = |mesh: &TriangleMesh| {
+            let Mat::Lambertain(mat) = &mesh.data().mat else {
+                panic!("materials without Ks or d should be Lambertian");
+            };
+            mat.albedo.value(0.0, 0.0, &Point3::default()).e
+        };
+        assert_eq!(albedo(&meshes[0]), DEFAULT_ALBEDO.e);
+        assert_eq!(albedo(&meshes[1]), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn rejects_unknown_material() {
+        let obj = format!("mtllib scene.mtl\n{SQUARE}usemtl blue\nf 1 2 3\n");
+        let Err(ObjError::Parse { line, message, .. }) = load("unknown", &obj, "newmtl red\nKd 1 0 0\n") else {
+            panic!("usemtl of an undefined material should be rejected");
+        };
+        assert_eq!(line, 6);
+        assert!(message.contains("'blue'"), "{message}");
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    convert::to_f32,
+    exr::{ExrOptions, write_exr},
+    image::Image,
+    png::write_png,
+    tonemap::DisplayTransform,
+};
+
+/// File format a rendered image is written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,             // Binary 8 bit RGB (P6)
+    Png,             // 8 bit RGB
+    Pfm,             // Linear 32 bit float RGB, unclamped
+    Exr(ExrOptions), // Linear OpenEXR, unclamped, including AOVs
+}
+
+impl ImageFormat {
+    /// Format matching the extension of `path`, ignoring case
+    #[must_use]
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(Self::Ppm),
+            "png" => Some(Self::Png),
+            "pfm" => Some(Self::Pfm),
+            "exr" => Some(Self::Exr(ExrOptions::default())),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Ppm => "ppm",
+            Self::Png => "png",
+            Self::Pfm => "pfm",
+            Self::Exr(_) => "exr",
+        }
+    }
+}
+
+/// Creates the file at `path` and writes `image` into it
+///
+/// # Errors
+///
+/// This function will return an error if the file cannot be created or written
+pub fn write_image(
+    path: impl AsRef<Path>,
+    image: &Image,
+    format: ImageFormat,
+    display: &DisplayTransform,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    encode_image(&mut out, image, format, display)?;
+    out.flush()
+}
+
+/// Writes `image` to `out` in the given format. 8 bit formats go through `display`, the float
+/// formats keep the linear values
+///
+/// # Errors
+///
+/// This function will return an error if writing to `out` fails
+pub fn encode_image(
+    out: &mut impl Write,
+    image: &Image,
+    format: ImageFormat,
+    display: &DisplayTransform,
+) -> io::Result<()> {
+    match format {
+        ImageFormat::Ppm => write_ppm(out, image, display),
+        ImageFormat::Png => write_png(out, image, display),
+        ImageFormat::Pfm => write_pfm(out, image),
+        ImageFormat::Exr(options) => write_exr(out, image, options),
+    }
+}
+
+fn write_ppm(out: &mut impl Write, image: &Image, display: &DisplayTransform) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", image.width(), image.height())?;
+    let bytes: Vec<u8> = image.pixels().iter().flat_map(|c| display.to_rgb8(c)).collect();
+    out.write_all(&bytes)
+}
+
+/// Little endian floats, stored bottom row first as the format requires
+fn write_pfm(out: &mut impl Write, image: &Image) -> io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+    let mut bytes = Vec::with_capacity(12 * image.pixels().len());
+    for row in image.pixels().chunks(image.width().max(1)).rev() {
+        for pixel in row {
+            for c in pixel.e {
+                bytes.extend_from_slice(&to_f32(c).to_le_bytes());
+            }
+        }
+    }
+    out.write_all(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    /// 2x2 image with a different color in every pixel, top row first
+    fn two_by_two() -> Image {
+        let pixels = vec![
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(2.5, 0.25, 0.0),
+        ];
+        Image::from_pixels(2, 2, pixels).expect("four pixels")
+    }
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_image(&mut out, &two_by_two(), format, &DisplayTransform::default()).expect("writing to memory");
+        out
+    }
+
+    #[test]
+    fn ppm_stores_rows_top_down() {
+        let bytes = encode(ImageFormat::Ppm);
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let expected = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 137, 0];
+        assert_eq!(&bytes[header.len()..], expected);
+    }
+
+    #[test]
+    fn pfm_stores_rows_bottom_up() {
+        let bytes = encode(ImageFormat::Pfm);
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let (data, rest) = bytes[header.len()..].as_chunks::<4>();
+        assert_eq!(rest, [0u8; 0]);
+        let floats: Vec<f32> = data.iter().map(|&b| f32::from_le_bytes(b)).collect();
+        let expected = [0.0, 0.0, 1.0, 2.5, 0.25, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        assert_eq!(floats, expected);
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(ImageFormat::from_path("out.ppm"), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_path("dir.d/out.PNG"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("out.pfm"), Some(ImageFormat::Pfm));
+        assert_eq!(ImageFormat::from_path("out.exr"), Some(ImageFormat::Exr(ExrOptions::default())));
+        assert_eq!(ImageFormat::from_path("out.jpg"), None);
+        assert_eq!(ImageFormat::from_path("out"), None);
+    }
+
+    #[test]
+    fn extension_round_trips() {
+        for format in [ImageFormat::Ppm, ImageFormat::Png, ImageFormat::Pfm, ImageFormat::Exr(ExrOptions::default())] {
+            assert_eq!(ImageFormat::from_path(format!("out.{}", format.extension())), Some(format));
+        }
+    }
+}
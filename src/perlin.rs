@@ -0,0 +1,196 @@
+use rand::{SeedableRng, seq::SliceRandom};
+
+use crate::{
+    RenderRng,
+    convert::to_usize,
+    vec3::{Point3, Vec3, dot, unit_vector},
+};
+
+const POINT_COUNT: usize = 256;
+
+/// Seeded Perlin gradient noise
+pub struct Perlin {
+    ranvec: [Vec3; POINT_COUNT], // Gradient vectors on the lattice points
+    perm_x: [usize; POINT_COUNT],
+    perm_y: [usize; POINT_COUNT],
+    perm_z: [usize; POINT_COUNT],
+}
+
+impl Perlin {
+    /// Noise with the gradients and lattice permutations drawn from `seed`,
+    /// the same seed always gives the same pattern
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        let mut rng = RenderRng::seed_from_u64(seed);
+        let ranvec = std::array::from_fn(|_| loop {
+            let v = Vec3::random_rng(&mut rng, -1.0, 1.0);
+            if !v.near_zero() {
+                break unit_vector(&v);
+            }
+        });
+        let mut perm = || {
+            let mut p: [usize; POINT_COUNT] = std::array::from_fn(|i| i);
+            p.shuffle(&mut rng);
+            p
+        };
+        let (perm_x, perm_y, perm_z) = (perm(), perm(), perm());
+        Self {
+            ranvec,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    /// Noise value in about -1..1, varying smoothly with a period of 256 units
+    #[must_use]
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let floor = Point3::new(p.x().floor(), p.y().floor(), p.z().floor());
+        let (ix, iy, iz) = (lattice(floor.x()), lattice(floor.y()), lattice(floor.z()));
+
+        let mut corners = [[[Vec3::default(); 2]; 2]; 2];
+        for (dx, plane) in corners.iter_mut().enumerate() {
+            for (dy, row) in plane.iter_mut().enumerate() {
+                for (dz, corner) in row.iter_mut().enumerate() {
+                    *corner = self.ranvec[self.perm_x[(ix + dx) & 255]
+                        ^ self.perm_y[(iy + dy) & 255]
+                        ^ self.perm_z[(iz + dz) & 255]];
+                }
+            }
+        }
+        perlin_interp(&corners, &(p - floor))
+    }
+
+    /// Sum of `depth` octaves of the absolute noise, each at double the frequency and half the weight
+    #[must_use]
+    pub fn turbulence(&self, p: &Point3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight: f64 = 1.0;
+        for _ in 0..depth {
+            accum = weight.mul_add(self.noise(&temp_p), accum);
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+        accum.abs()
+    }
+
+    /// Fractal Brownian motion, `octaves` of noise with frequency scaled by `lacunarity`
+    /// and amplitude scaled by `gain` per octave, normalized back to about -1..1
+    #[must_use]
+    pub fn fbm(&self, p: &Point3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+        let mut sum = 0.0;
+        let mut norm = 0.0;
+        let mut amplitude: f64 = 1.0;
+        let mut temp_p = *p;
+        for _ in 0..octaves {
+            sum = amplitude.mul_add(self.noise(&temp_p), sum);
+            norm += amplitude;
+            amplitude *= gain;
+            temp_p *= lacunarity;
+        }
+        if norm > 0.0 { sum / norm } else { 0.0 }
+    }
+}
+
+/// Trilinear blend of the corner gradients at the offset `f` into the lattice cell,
+/// smoothed with a Hermite cubic
+fn perlin_interp(corners: &[[[Vec3; 2]; 2]; 2], f: &Vec3) -> f64 {
+    let hermite = |t: f64| t * t * 2.0f64.mul_add(-t, 3.0);
+    let smooth = Vec3::new(hermite(f.x()), hermite(f.y()), hermite(f.z()));
+
+    let mut accum = 0.0;
+    for (dx, plane) in corners.iter().enumerate() {
+        for (dy, row) in plane.iter().enumerate() {
+            for (dz, corner) in row.iter().enumerate() {
+                let offset = Vec3::new(corner_index(dx), corner_index(dy), corner_index(dz));
+                let weight = blend(offset.x(), smooth.x()) * blend(offset.y(), smooth.y()) * blend(offset.z(), smooth.z());
+                accum = weight.mul_add(dot(corner, &(f - offset)), accum);
+            }
+        }
+    }
+    accum
+}
+
+/// Weight of the lower (0) or upper (1) lattice point for the smoothed offset `t`
+fn blend(corner: f64, t: f64) -> f64 {
+    corner.mul_add(t, (1.0 - corner) * (1.0 - t))
+}
+
+const fn corner_index(i: usize) -> f64 {
+    if i == 0 { 0.0 } else { 1.0 }
+}
+
+/// Lattice coordinate wrapped into the permutation tables
+fn lattice(floor: f64) -> usize {
+    to_usize(floor.rem_euclid(256.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    /// Points scattered over a few hundred lattice cells, negative coordinates included
+    fn points() -> Vec<Point3> {
+        let mut rng = RenderRng::seed_from_u64(11);
+        (0..2000)
+            .map(|_| Point3::new(rng.random_range(-40.0..40.0), rng.random_range(-40.0..40.0), rng.random_range(-40.0..40.0)))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_noise() {
+        let (a, b, c) = (Perlin::new(4), Perlin::new(4), Perlin::new(5));
+        let values = |perlin: &Perlin| -> Vec<u64> { points().iter().map(|p| perlin.noise(p).to_bits()).collect() };
+        assert_eq!(values(&a), values(&b));
+        assert_ne!(values(&a), values(&c));
+    }
+
+    #[test]
+    fn noise_stays_within_unit_range() {
+        let perlin = Perlin::new(4);
+        for p in points() {
+            let n = perlin.noise(&p);
+            assert!((-1.0..=1.0).contains(&n), "noise {n} at {:?}", p.e);
+        }
+    }
+
+    #[test]
+    fn noise_is_zero_on_lattice_points() {
+        let perlin = Perlin::new(4);
+        for (x, y, z) in [(0.0, 0.0, 0.0), (3.0, -7.0, 12.0), (-255.0, 256.0, 1000.0)] {
+            let n = perlin.noise(&Point3::new(x, y, z));
+            assert!(n.abs() < 1e-12, "noise {n} at ({x}, {y}, {z})");
+        }
+    }
+
+    #[test]
+    fn noise_repeats_every_256_units() {
+        let perlin = Perlin::new(4);
+        for p in points().iter().take(100) {
+            let shifted = p + Vec3::new(256.0, -256.0, 512.0);
+            assert!((perlin.noise(p) - perlin.noise(&shifted)).abs() < 1e-9, "at {:?}", p.e);
+        }
+    }
+
+    #[test]
+    fn turbulence_is_never_negative() {
+        let perlin = Perlin::new(4);
+        for p in points() {
+            let t = perlin.turbulence(&p, 7);
+            assert!(t >= 0.0, "turbulence {t} at {:?}", p.e);
+        }
+    }
+
+    #[test]
+    fn fbm_stays_within_unit_range() {
+        let perlin = Perlin::new(4);
+        for p in points() {
+            let n = perlin.fbm(&p, 6, 2.0, 0.5);
+            assert!((-1.0..=1.0).contains(&n), "fbm {n} at {:?}", p.e);
+        }
+        assert!(perlin.fbm(&Point3::new(0.5, 0.5, 0.5), 0, 2.0, 0.5).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,177 @@
+use std::io::{self, Write};
+
+use crate::{image::Image, tonemap::DisplayTransform, zlib};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Writes `image` as an 8 bit RGB PNG, converted to display values by `display`
+///
+/// # Errors
+///
+/// This function will return an error if writing to `out` fails, or if the image is larger than
+/// PNG allows
+pub fn write_png(out: &mut impl Write, image: &Image, display: &DisplayTransform) -> io::Result<()> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "image too large for PNG");
+    let width = u32::try_from(image.width()).map_err(|_| too_large())?;
+    let height = u32::try_from(image.height()).map_err(|_| too_large())?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]); // 8 bit depth, RGB, deflate, adaptive filtering, no interlace
+
+    out.write_all(&SIGNATURE)?;
+    write_chunk(out, *b"IHDR", &header)?;
+    write_chunk(out, *b"IDAT", &zlib::compress(&filtered_scanlines(image, display)))?;
+    write_chunk(out, *b"IEND", &[])
+}
+
+fn write_chunk(out: &mut impl Write, kind: [u8; 4], data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "PNG chunk too large"))?;
+    let mut crc_input = Vec::with_capacity(4 + data.len());
+    crc_input.extend_from_slice(&kind);
+    crc_input.extend_from_slice(data);
+    out.write_all(&len.to_be_bytes())?;
+    out.write_all(&crc_input)?;
+    out.write_all(&zlib::crc32(&crc_input).to_be_bytes())
+}
+
+/// Scanlines each prefixed with the filter type that minimises the sum of absolute differences,
+/// the usual heuristic for picking a filter that compresses well
+fn filtered_scanlines(image: &Image, display: &DisplayTransform) -> Vec<u8> {
+    let stride = 3 * image.width();
+    let mut previous = vec![0_u8; stride];
+    let mut current = Vec::with_capacity(stride);
+    let mut out = Vec::with_capacity((stride + 1) * image.height());
+    let mut candidate = vec![0_u8; stride];
+    let mut best = vec![0_u8; stride];
+    for row in image.pixels().chunks(image.width().max(1)) {
+        current.clear();
+        current.extend(row.iter().flat_map(|c| display.to_rgb8(c)));
+        let mut best_filter = 0;
+        let mut best_score = usize::MAX;
+        for filter in 0..5 {
+            apply_filter(filter, &current, &previous, &mut candidate);
+            let score = candidate.iter().map(|&b| usize::from(b.cast_signed().unsigned_abs())).sum();
+            if score < best_score {
+                best_score = score;
+                best_filter = filter;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+        out.push(best_filter);
+        out.extend_from_slice(&best);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    out
+}
+
+/// Filters a scanline of 3 byte pixels, `filter` is the PNG filter type 0 to 4
+fn apply_filter(filter: u8, row: &[u8], above: &[u8], out: &mut [u8]) {
+    for i in 0..row.len() {
+        let left = if i >= 3 { row[i - 3] } else { 0 };
+        let upper_left = if i >= 3 { above[i - 3] } else { 0 };
+        let up = above[i];
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => u8::midpoint(left, up),
+            _ => paeth(left, up, upper_left),
+        };
+        out[i] = row[i].wrapping_sub(predicted);
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+    /// Chunks of a PNG file as `(kind, data)`, checking each CRC
+    fn chunks(mut bytes: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut chunks = Vec::new();
+        while !bytes.is_empty() {
+            let (len, rest) = bytes.split_at(4);
+            let len = usize::try_from(u32::from_be_bytes(len.try_into().expect("4 bytes"))).expect("fits");
+            let (body, rest) = rest.split_at(4 + len);
+            let (crc, rest) = rest.split_at(4);
+            assert_eq!(zlib::crc32(body).to_be_bytes(), crc);
+            let kind = body[..4].try_into().expect("4 bytes");
+            chunks.push((kind, body[4..].to_vec()));
+            bytes = rest;
+        }
+        chunks
+    }
+
+    /// Reverses the filtering of 3 byte pixel scanlines `stride` bytes long
+    fn unfilter(data: &[u8], stride: usize) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::with_capacity(data.len());
+        for (y, line) in data.chunks(stride + 1).enumerate() {
+            let start = out.len();
+            for (i, &byte) in line[1..].iter().enumerate() {
+                let left = if i >= 3 { out[start + i - 3] } else { 0 };
+                let up = if y > 0 { out[start + i - stride] } else { 0 };
+                let upper_left = if y > 0 && i >= 3 { out[start + i - stride - 3] } else { 0 };
+                let predicted = match line[0] {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => u8::midpoint(left, up),
+                    4 => paeth(left, up, upper_left),
+                    filter => panic!("unknown filter type {filter}"),
+                };
+                out.push(byte.wrapping_add(predicted));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn decodes_to_display_values() {
+        let (width, height) = (37, 23);
+        let mut image = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let (fx, fy) = (f64::from(u8::try_from(x).expect("fits")), f64::from(u8::try_from(y).expect("fits")));
+                image.set(x, y, Color::new(fx / 36.0, fy / 22.0, ((fx * fy) % 7.0) / 6.0));
+            }
+        }
+        let display = DisplayTransform::default();
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, &image, &display).expect("writing to a Vec cannot fail");
+
+        assert_eq!(bytes[..8], SIGNATURE);
+        let chunks = chunks(&bytes[8..]);
+        let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 37, 0, 0, 0, 23, 8, 2, 0, 0, 0]);
+
+        let scanlines = decompress_to_vec_zlib(&chunks[1].1).expect("IDAT should inflate");
+        let expected: Vec<u8> = image.pixels().iter().flat_map(|c| display.to_rgb8(c)).collect();
+        assert_eq!(unfilter(&scanlines, 3 * width), expected);
+    }
+
+    #[test]
+    fn paeth_picks_nearest_neighbour() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+        assert_eq!(paeth(50, 60, 55), 55);
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{convert::{to_f64, to_usize}, tile::Tile};
+
+const BAR_WIDTH: usize = 30;
+
+/// State of a render after a tile completes
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    pub tile: Tile,            // Tile that just completed
+    pub pass: usize,           // Pass `tile` was rendered in, counting from 0
+    pub tiles_done: usize,     // Tiles completed so far over all passes, including `tile`
+    pub tiles_total: usize,    // Tiles in all passes, or in the passes started so far if open ended
+    pub elapsed: Duration,     // Time since rendering started
+    pub eta: Option<Duration>, // Estimated time left, `None` until it can be guessed
+}
+
+impl Progress {
+    /// Progress with the ETA extrapolated from the average time per tile so far
+    #[must_use]
+    pub fn new(tile: Tile, pass: usize, tiles_done: usize, tiles_total: usize, elapsed: Duration) -> Self {
+        let eta = (tiles_done > 0)
+            .then(|| elapsed.mul_f64(to_f64(tiles_total.saturating_sub(tiles_done)) / to_f64(tiles_done)));
+        Self {
+            tile,
+            pass,
+            tiles_done,
+            tiles_total,
+            elapsed,
+            eta,
+        }
+    }
+
+    /// Fraction of the render completed, from 0 to 1. Based on the time left when that is
+    /// known, which for an extrapolated ETA is the same as the fraction of tiles done
+    #[must_use]
+    pub fn fraction(&self) -> f64 {
+        match self.eta {
+            Some(eta) if !(self.elapsed + eta).is_zero() => {
+                self.elapsed.as_secs_f64() / (self.elapsed + eta).as_secs_f64()
+            }
+            _ if self.tiles_total == 0 => 1.0,
+            _ => to_f64(self.tiles_done) / to_f64(self.tiles_total),
+        }
+    }
+}
+
+/// Receives updates while a render runs. Calls come from the thread that called `render`,
+/// one at a time
+pub trait ProgressSink: Send + Sync {
+    fn tile_done(&self, progress: &Progress);
+
+    /// Called once after the last tile, with the total render time
+    fn finished(&self, _elapsed: Duration) {}
+}
+
+impl<F: Fn(&Progress) + Send + Sync> ProgressSink for F {
+    fn tile_done(&self, progress: &Progress) {
+        self(progress);
+    }
+}
+
+/// Progress bar drawn on stderr, so it stays out of anything piped from stdout
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TerminalProgress;
+
+impl ProgressSink for TerminalProgress {
+    fn tile_done(&self, progress: &Progress) {
+        let fraction = progress.fraction();
+        let filled = to_usize(fraction * to_f64(BAR_WIDTH)).min(BAR_WIDTH);
+        let eta = progress
+            .eta
+            .map_or_else(|| "--".to_string(), |eta| format!("{:.1}s", eta.as_secs_f64()));
+        let mut err = io::stderr().lock();
+        // Progress output is best effort and must not fail the render
+        write!(
+            err,
+            "\r[{}{}] {:>3.0}% pass {}, {}/{} tiles, {:.1}s elapsed, {eta} left ",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            100.0 * fraction,
+            progress.pass + 1,
+            progress.tiles_done,
+            progress.tiles_total,
+            progress.elapsed.as_secs_f64(),
+        )
+        .ok();
+        err.flush().ok();
+    }
+
+    fn finished(&self, elapsed: Duration) {
+        let mut err = io::stderr().lock();
+        writeln!(err, "\rDone in {:.1}s{:<60}", elapsed.as_secs_f64(), "").ok();
+    }
+}
+
+/// Ignores all progress
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SilentProgress;
+
+impl ProgressSink for SilentProgress {
+    fn tile_done(&self, _progress: &Progress) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn tile() -> Tile {
+        Tile { x0: 0, y0: 0, x1: 8, y1: 8 }
+    }
+
+    #[test]
+    fn eta_extrapolates_time_per_tile() {
+        let progress = Progress::new(tile(), 0, 3, 12, Duration::from_secs(6));
+        assert_eq!(progress.eta, Some(Duration::from_secs(18)));
+        assert!((progress.fraction() - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn eta_is_unknown_before_any_tile() {
+        let progress = Progress::new(tile(), 0, 0, 12, Duration::ZERO);
+        assert_eq!(progress.eta, None);
+        assert!(progress.fraction().abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn fraction_follows_given_eta() {
+        let mut progress = Progress::new(tile(), 1, 3, 3, Duration::from_secs(1));
+        progress.eta = Some(Duration::from_secs(3));
+        assert!((progress.fraction() - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn empty_render_is_complete() {
+        let progress = Progress::new(tile(), 0, 0, 0, Duration::ZERO);
+        assert!((progress.fraction() - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn closures_are_sinks() {
+        let calls = AtomicUsize::new(0);
+        let sink = |_: &Progress| {
+            calls.fetch_add(1, Ordering::Relaxed);
+        };
+        sink.tile_done(&Progress::new(tile(), 0, 1, 2, Duration::ZERO));
+        sink.finished(Duration::ZERO);
+        assert_eq!(calls.into_inner(), 1);
+    }
+}
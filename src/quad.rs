@@ -0,0 +1,226 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, HitRecord},
+    internal::Interval,
+    material::Mat,
+    ray::Ray,
+    vec3::{
+        Point3,
+        Vec3,
+        cross,
+        dot,
+        unit_vector,
+    },
+};
+
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`
+#[derive(Clone)]
+pub struct Quad {
+    pub(super) q: Point3,
+    pub(super) u: Vec3,
+    pub(super) v: Vec3,
+    pub(super) w: Vec3,      // Scaled plane normal, maps plane points to (alpha, beta)
+    pub(super) normal: Vec3, // Unit plane normal, front face is the side `u x v` points to
+    pub(super) d: f64,       // Plane offset, dot(normal, p) == d for points on the plane
+    pub mat: Mat,
+}
+
+impl Quad {
+    #[must_use]
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Mat) -> Self {
+        let n = cross(&u, &v);
+        let normal = unit_vector(&n);
+        Self {
+            q,
+            u,
+            v,
+            w: n / dot(&n, &n),
+            normal,
+            d: dot(&normal, &q),
+            mat,
+        }
+    }
+
+    #[must_use]
+    pub const fn corner(&self) -> &Point3 {
+        &self.q
+    }
+
+    #[must_use]
+    pub const fn edges(&self) -> (&Vec3, &Vec3) {
+        (&self.u, &self.v)
+    }
+}
+
+impl Hit for Quad {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        let denom = dot(&self.normal, r.direction());
+        // Ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = (self.d - dot(&self.normal, r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        // Express the hit point in the plane coordinates of the edges
+        let intersection = r.at(t);
+        let planar_hitpt = intersection - self.q;
+        let alpha = dot(&self.w, &cross(&planar_hitpt, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar_hitpt));
+        let unit_interval = Interval::new(0.0, &1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.mat = &self.mat;
+        rec.set_face_normal(r, self.normal);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let diagonal1 = Aabb::from_points(&self.q, &(self.q + self.u + self.v));
+        let diagonal2 = Aabb::from_points(&(self.q + self.u), &(self.q + self.v));
+        Aabb::surrounding(&diagonal1, &diagonal2).pad_to_minimums()
+    }
+}
+
+/// The six faces of the axis aligned box with opposite corners `a` and `b`, normals facing out
+#[must_use]
+pub fn make_box(a: &Point3, b: &Point3, mat: &Mat) -> [Quad; 6] {
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    [
+        Quad::new(Point3::new(min.x(), min.y(), max.z()), dx, dy, mat.clone()), // front
+        Quad::new(Point3::new(max.x(), min.y(), max.z()), -dz, dy, mat.clone()), // right
+        Quad::new(Point3::new(max.x(), min.y(), min.z()), -dx, dy, mat.clone()), // back
+        Quad::new(Point3::new(min.x(), min.y(), min.z()), dz, dy, mat.clone()), // left
+        Quad::new(Point3::new(min.x(), max.y(), max.z()), dx, -dz, mat.clone()), // top
+        Quad::new(Point3::new(min.x(), min.y(), min.z()), dx, dz, mat.clone()), // bottom
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertain, texture::Texture};
+
+    fn grey() -> Mat {
+        Mat::Lambertain(Lambertain {
+            albedo: Texture::Solid(Color::new(0.5, 0.5, 0.5)),
+        })
+    }
+
+    /// Unit square in the z = 0 plane, facing +z
+    fn unit_square() -> Quad {
+        Quad::new(Point3::default(), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), grey())
+    }
+
+    fn hit<'a>(quad: &'a Quad, origin: &Point3, direction: &Vec3) -> Option<HitRecord<'a>> {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(origin, direction);
+        quad.hit(&r, &Interval::new(0.001, &f64::INFINITY), &mut rec).then_some(rec)
+    }
+
+    fn assert_close(actual: &Vec3, expected: &Vec3) {
+        assert!((*actual - *expected).len() < 1e-12, "{:?} is not {:?}", actual.e, expected.e);
+    }
+
+    #[test]
+    fn hits_inside() {
+        let quad = unit_square();
+        let rec = hit(&quad, &Point3::new(0.25, 0.5, 2.0), &Vec3::new(0.0, 0.0, -1.0)).expect("ray hits the quad");
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert_close(&rec.p, &Point3::new(0.25, 0.5, 0.0));
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn misses_outside() {
+        let quad = unit_square();
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        for origin in [Point3::new(1.5, 0.5, 1.0), Point3::new(0.5, -0.1, 1.0), Point3::new(-2.0, 3.0, 1.0)] {
+            assert!(hit(&quad, &origin, &down).is_none(), "hit from {:?}", origin.e);
+        }
+    }
+
+    #[test]
+    fn misses_parallel_ray() {
+        let quad = unit_square();
+        assert!(hit(&quad, &Point3::new(-1.0, 0.5, 0.0), &Vec3::new(1.0, 0.0, 0.0)).is_none());
+        assert!(hit(&quad, &Point3::new(-1.0, 0.5, 0.5), &Vec3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn uv_at_corners() {
+        let quad = Quad::new(Point3::new(1.0, 2.0, 0.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 3.0, 0.0), grey());
+        for (u, v) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+            let corner = Point3::new(2.0f64.mul_add(u, 1.0), 3.0f64.mul_add(v, 2.0), 1.0);
+            let rec = hit(&quad, &corner, &Vec3::new(0.0, 0.0, -1.0)).expect("corners are part of the quad");
+            assert!((rec.u - u).abs() < 1e-12 && (rec.v - v).abs() < 1e-12, "({u}, {v}) gives ({}, {})", rec.u, rec.v);
+        }
+    }
+
+    #[test]
+    fn normal_faces_the_ray() {
+        let quad = unit_square();
+        let front = hit(&quad, &Point3::new(0.5, 0.5, 1.0), &Vec3::new(0.0, 0.0, -1.0)).expect("hit from the front");
+        assert!(front.front_face);
+        assert_close(&front.normal, &Vec3::new(0.0, 0.0, 1.0));
+        let back = hit(&quad, &Point3::new(0.5, 0.5, -1.0), &Vec3::new(0.0, 0.0, 1.0)).expect("hit from the back");
+        assert!(!back.front_face);
+        assert_close(&back.normal, &Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn box_faces_point_out() {
+        // Corners given in no particular order
+        let (a, b) = (Point3::new(3.0, -1.0, 2.0), Point3::new(1.0, 2.0, -2.0));
+        let center = 0.5 * (a + b);
+        let faces = make_box(&a, &b, &grey());
+        let mut directions: Vec<[f64; 3]> = faces
+            .iter()
+            .map(|face| {
+                let face_center = face.q + 0.5 * (face.u + face.v);
+                let outward = face_center - center;
+                assert!((dot(&face.normal, &outward) - outward.len()).abs() < 1e-12, "normal {:?}", face.normal.e);
+                face.normal.e
+            })
+            .collect();
+        directions.sort_by(|x, y| x.partial_cmp(y).expect("normals are finite"));
+        let expected = [
+            [-1.0, 0.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.0, 0.0, -1.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ];
+        assert_eq!(directions, expected);
+    }
+
+    #[test]
+    fn box_faces_cover_box() {
+        let faces = make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(1.0, 2.0, 3.0), &grey());
+        let area: f64 = faces.iter().map(|face| cross(&face.u, &face.v).len()).sum();
+        assert!((area - 22.0).abs() < 1e-12, "surface area {area}");
+        for face in &faces {
+            let bbox = face.bounding_box();
+            for axis in 0..3 {
+                let extent = bbox.axis_interval(axis);
+                assert!(extent.min >= -1e-3 && extent.max <= [1.0, 2.0, 3.0][axis] + 1e-3);
+            }
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::sync::OnceLock;
+
+use rand::{Rng, SeedableRng};
+
+use crate::{RenderRng, convert::{low_bits, to_f64}};
+
+/// Where the sample values used for pixel positions, lens positions and bounce directions
+/// come from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    #[default]
+    Independent, // Uniform random numbers
+    Stratified,  // Jittered within a grid of strata, one stratum per sample of a pixel
+    Halton,      // Halton sequence, shifted randomly per pixel and dimension
+    Sobol,       // 2D Sobol points with Owen scrambling and shuffling per pixel and dimension
+    BlueNoise,   // Low discrepancy sequence offset per pixel by a blue noise mask, spreading error evenly
+}
+
+/// Source of the sample values for one sample of one pixel
+///
+/// Every call takes the next dimension of the sample, so values drawn in the same order by
+/// different samples of a pixel are spread over the unit square together rather than apart
+pub struct Sampler {
+    kind: SamplerKind,
+    pixel: [u32; 2],
+    index: u32,             // Sample number within the pixel
+    samples_per_pixel: u32, // Samples a stratified pixel spreads its strata over
+    dimension: u32,         // Next dimension to draw
+    image_seed: u64,        // Hash of the seed alone, shared by every pixel
+    pixel_seed: u64,        // Hash of the seed and pixel, shared by every sample of the pixel
+    rng: RenderRng,
+}
+
+impl Sampler {
+    /// Sampler for sample `index` of `pixel`, with the same values every time for the same
+    /// arguments so the numbers a sample sees do not depend on which thread renders it or when
+    #[must_use]
+    pub fn new(kind: SamplerKind, seed: u64, pixel: [u32; 2], index: u32, samples_per_pixel: u32) -> Self {
+        let image_seed = splitmix64(seed);
+        let pixel_seed = pixel.into_iter().fold(image_seed, |h, k| splitmix64(h ^ u64::from(k)));
+        Self {
+            kind,
+            pixel,
+            index,
+            samples_per_pixel: samples_per_pixel.max(1),
+            dimension: 0,
+            image_seed,
+            pixel_seed,
+            rng: RenderRng::seed_from_u64(splitmix64(pixel_seed ^ u64::from(index))),
+        }
+    }
+
+    #[must_use]
+    pub const fn kind(&self) -> SamplerKind {
+        self.kind
+    }
+
+    /// Random number generator for anything that needs more than a fixed number of values,
+    /// such as rejection sampling
+    pub const fn rng(&mut self) -> &mut RenderRng {
+        &mut self.rng
+    }
+
+    /// Next value in `[0, 1)`
+    pub fn next_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        match self.kind {
+            SamplerKind::Independent => self.rng.random::<f64>(),
+            SamplerKind::Stratified => {
+                let strata = self.samples_per_pixel;
+                let stratum = self.stratum(dimension, strata);
+                (f64::from(stratum) + self.rng.random::<f64>()) / f64::from(strata)
+            }
+            SamplerKind::Halton => self.halton(dimension),
+            SamplerKind::Sobol => self.sobol(dimension).0,
+            SamplerKind::BlueNoise => {
+                let offset = self.blue_noise_offset(dimension);
+                f64::from(self.index).mul_add(GOLDEN_RATIO_FRACTION, offset).fract()
+            }
+        }
+    }
+
+    /// Next point in `[0, 1)²`
+    pub fn next_2d(&mut self) -> (f64, f64) {
+        let dimension = self.dimension;
+        self.dimension += 2;
+        match self.kind {
+            SamplerKind::Independent => (self.rng.random::<f64>(), self.rng.random::<f64>()),
+            SamplerKind::Stratified => {
+                let side = self.samples_per_pixel.isqrt()
+                    + u32::from(self.samples_per_pixel.isqrt().pow(2) < self.samples_per_pixel);
+                let stratum = self.stratum(dimension, side * side);
+                let (x, y) = (stratum % side, stratum / side);
+                (
+                    (f64::from(x) + self.rng.random::<f64>()) / f64::from(side),
+                    (f64::from(y) + self.rng.random::<f64>()) / f64::from(side),
+                )
+            }
+            SamplerKind::Halton => (self.halton(dimension), self.halton(dimension + 1)),
+            SamplerKind::Sobol => self.sobol(dimension),
+            SamplerKind::BlueNoise => {
+                let n = f64::from(self.index);
+                let (offset_x, offset_y) = (self.blue_noise_offset(dimension), self.blue_noise_offset(dimension + 1));
+                (R2_X.mul_add(n, offset_x).fract(), R2_Y.mul_add(n, offset_y).fract())
+            }
+        }
+    }
+
+    /// Stratum out of `strata` for this sample, every run of `strata` samples of the pixel
+    /// covering each stratum once in an order shuffled per dimension
+    fn stratum(&self, dimension: u32, strata: u32) -> u32 {
+        let (round, position) = (self.index / strata, self.index % strata);
+        let hash = splitmix64(self.pixel_seed ^ splitmix64((u64::from(dimension) << 32) | u64::from(round)));
+        permute(position, strata, low_bits(hash))
+    }
+
+    /// Halton value for this sample, falling back to random numbers past the table of primes
+    fn halton(&mut self, dimension: u32) -> f64 {
+        let Some(&base) = PRIMES.get(dimension as usize) else {
+            return self.rng.random::<f64>();
+        };
+        let shift = to_unit(low_bits(splitmix64(self.pixel_seed ^ u64::from(dimension))));
+        (radical_inverse(base, self.index) + shift).fract()
+    }
+
+    /// Padded 2D Sobol point, see Burley's "Practical Hash-based Owen Scrambling"
+    fn sobol(&self, dimension: u32) -> (f64, f64) {
+        let hash = splitmix64(self.pixel_seed ^ u64::from(dimension));
+        let index = nested_uniform_scramble(self.index, low_bits(hash));
+        let x = nested_uniform_scramble(index.reverse_bits(), low_bits(hash >> 32));
+        let y = nested_uniform_scramble(sobol_second_dimension(index), low_bits(splitmix64(hash)));
+        (to_unit(x), to_unit(y))
+    }
+
+    /// Blue noise mask value for this pixel, in a tile shifted per seed and dimension. The shift
+    /// is the same for every pixel, as shifting pixels apart would lose the blue noise arrangement
+    fn blue_noise_offset(&self, dimension: u32) -> f64 {
+        let hash = splitmix64(self.image_seed ^ u64::from(dimension));
+        let x = (self.pixel[0] as usize + low_bits(hash) as usize) % BLUE_NOISE_SIZE;
+        let y = (self.pixel[1] as usize + low_bits(hash >> 32) as usize) % BLUE_NOISE_SIZE;
+        blue_noise_mask()[y * BLUE_NOISE_SIZE + x]
+    }
+}
+
+const GOLDEN_RATIO_FRACTION: f64 = 0.618_033_988_749_894_8;
+// Steps of Roberts' R2 sequence, the 2D analogue of the golden ratio
+const R2_X: f64 = 0.754_877_666_246_692_7;
+const R2_Y: f64 = 0.569_840_290_998_053_2;
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109,
+    113, 127, 131,
+];
+
+/// Digits of `index` in `base` mirrored around the radix point
+fn radical_inverse(base: u32, mut index: u32) -> f64 {
+    let inverse_base = 1.0 / f64::from(base);
+    let (mut reversed, mut scale) = (0.0, inverse_base);
+    while index > 0 {
+        reversed = f64::from(index % base).mul_add(scale, reversed);
+        index /= base;
+        scale *= inverse_base;
+    }
+    reversed.min(1.0 - f64::EPSILON)
+}
+
+/// Second dimension of the Sobol sequence, with the bits in the order of `reverse_bits` of
+/// the first
+const fn sobol_second_dimension(mut index: u32) -> u32 {
+    let (mut result, mut v) = (0, 1 << 31);
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+/// Owen scrambling of the bits of `x` from the most significant down
+const fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash in which every bit only depends on the bits below it
+const fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+/// Element `i` of a permutation of `0..len` picked by `seed`, from Kensler's "Correlated
+/// Multi-Jittered Sampling"
+const fn permute(mut i: u32, len: u32, seed: u32) -> u32 {
+    let mut mask = len.saturating_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | (seed >> 27));
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= mask;
+        i ^= i >> 5;
+        // The hash permutes `0..=mask`, so walking its cycle lands back inside `0..len`
+        if i < len {
+            break;
+        }
+    }
+    i.wrapping_add(seed) % len
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+/// Tileable `BLUE_NOISE_SIZE` square mask of values evenly spread over `[0, 1)`, made once on
+/// first use
+fn blue_noise_mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+/// Ulichney's void and cluster method: pixels are ranked by repeatedly filling the largest
+/// gap, measured with a Gaussian energy that wraps around the edges
+fn void_and_cluster() -> Vec<f64> {
+    const SIGMA: f64 = 1.5;
+    const RADIUS: isize = 6;
+    let n = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+    let kernel: Vec<(isize, isize, f64)> = (-RADIUS..=RADIUS)
+        .flat_map(|dy| (-RADIUS..=RADIUS).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| (dx, dy, (-to_f64((dx * dx + dy * dy).unsigned_abs()) / (2.0 * SIGMA * SIGMA)).exp()))
+        .collect();
+    let toggle = |ones: &mut [bool], energy: &mut [f64], p: usize, on: bool| {
+        ones[p] = on;
+        let (x, y) = ((p % BLUE_NOISE_SIZE).cast_signed(), (p / BLUE_NOISE_SIZE).cast_signed());
+        let size = BLUE_NOISE_SIZE.cast_signed();
+        for &(dx, dy, w) in &kernel {
+            let q = (y + dy).rem_euclid(size) * size + (x + dx).rem_euclid(size);
+            energy[q.cast_unsigned()] += if on { w } else { -w };
+        }
+    };
+    // Tightest cluster among the set pixels, or largest void among the unset ones
+    let extreme = |ones: &[bool], energy: &[f64], set: bool| {
+        (0..n)
+            .filter(|&p| ones[p] == set)
+            .reduce(|a, b| {
+                let better = if set { energy[b] > energy[a] } else { energy[b] < energy[a] };
+                if better { b } else { a }
+            })
+            .unwrap_or(0)
+    };
+
+    let (mut ones, mut energy) = (vec![false; n], vec![0.0; n]);
+    let initial = n / 10;
+    let mut state = 0;
+    let mut placed = 0;
+    while placed < initial {
+        state = splitmix64(state);
+        let p = low_bits(state) as usize % n;
+        if !ones[p] {
+            toggle(&mut ones, &mut energy, p, true);
+            placed += 1;
+        }
+    }
+    // Move points from clusters into voids until the pattern is even
+    loop {
+        let cluster = extreme(&ones, &energy, true);
+        toggle(&mut ones, &mut energy, cluster, false);
+        let void = extreme(&ones, &energy, false);
+        toggle(&mut ones, &mut energy, void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; n];
+    let (mut phase_ones, mut phase_energy) = (ones.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster = extreme(&phase_ones, &phase_energy, true);
+        toggle(&mut phase_ones, &mut phase_energy, cluster, false);
+        rank[cluster] = r;
+    }
+    for r in initial..n {
+        let void = extreme(&ones, &energy, false);
+        toggle(&mut ones, &mut energy, void, true);
+        rank[void] = r;
+    }
+    rank.into_iter().map(|r| (to_f64(r) + 0.5) / to_f64(n)).collect()
+}
+
+/// Scrambles a 64 bit value so nearby inputs give unrelated outputs
+const fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Fixed point fraction of 32 bits as a float in `[0, 1)`
+fn to_unit(x: u32) -> f64 {
+    f64::from(x) / 4_294_967_296.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 5] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+        SamplerKind::BlueNoise,
+    ];
+
+    /// First values drawn by every sample of a small block of pixels
+    fn stream(kind: SamplerKind, seed: u64) -> Vec<f64> {
+        let mut values = Vec::new();
+        for (x, y, index) in (0..4).flat_map(|x| (0..4).flat_map(move |y| (0..8).map(move |i| (x, y, i)))) {
+            let mut sampler = Sampler::new(kind, seed, [x, y], index, 8);
+            let (u, v) = sampler.next_2d();
+            values.extend([u, v, sampler.next_1d()]);
+        }
+        values
+    }
+
+    #[test]
+    fn same_seed_gives_same_values() {
+        for kind in KINDS {
+            assert_eq!(stream(kind, 7), stream(kind, 7), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_values() {
+        for kind in KINDS {
+            assert_ne!(stream(kind, 0), stream(kind, 99), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn values_lie_in_unit_interval() {
+        for kind in KINDS {
+            assert!(stream(kind, 3).iter().all(|v| (0.0..1.0).contains(v)), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn stratified_covers_every_stratum() {
+        // Column or row out of 4 that a value falls in
+        let cell = |x: f64| (1..4).filter(|&k| f64::from(k) <= 4.0 * x).count();
+        let mut strata: Vec<usize> = (0..16)
+            .map(|index| {
+                let (u, v) = Sampler::new(SamplerKind::Stratified, 1, [2, 3], index, 16).next_2d();
+                cell(u) + 4 * cell(v)
+            })
+            .collect();
+        strata.sort_unstable();
+        assert_eq!(strata, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn permute_is_a_permutation() {
+        for len in [1, 2, 5, 16, 33] {
+            let mut values: Vec<u32> = (0..len).map(|i| permute(i, len, 0x1234_5678)).collect();
+            values.sort_unstable();
+            assert_eq!(values, (0..len).collect::<Vec<_>>());
+        }
+    }
+}
@@ -1,56 +1,75 @@
+use std::f64::consts::PI;
+
 use crate::{
-    hittable::HitRecord,
+    aabb::Aabb,
+    hittable::{Hit, HitRecord},
     internal::Interval,
     material::Mat,
     ray::Ray,
     vec3::{
         Point3,
+        Vec3,
         dot,
     },
 };
 
-#[derive(Clone, Copy)]
-pub enum Hittable {
-    Sphere(Point3, f64, Mat),
-    Empty,
+#[derive(Clone)]
+pub struct Sphere {
+    pub center: Point3,
+    pub radius: f64,
+    pub mat: Mat,
 }
 
-impl Hittable {
-    pub fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
-        match self {
-            Self::Sphere(center, radius, mat) => sphere_hit(*center, *radius, *mat, r, ray_t, rec),
-            Self::Empty => false,
-        }
+impl Sphere {
+    #[must_use]
+    pub const fn new(center: Point3, radius: f64, mat: Mat) -> Self {
+        Self { center, radius, mat }
     }
 }
 
+impl Hit for Sphere {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        let oc: Point3 = self.center - r.origin();
+        let a: f64 = r.direction().len_squared();
+        let h: f64 = dot(r.direction(), &oc);
+        let c: f64 = self.radius.mul_add(-self.radius, oc.len_squared());
 
-fn sphere_hit(center: Point3, radius: f64, mat: Mat, r: &Ray, ray_t: &Interval, rec: &mut HitRecord) -> bool {
-    let oc: Point3 = center - r.origin();
-    let a: f64 = r.direction().len_squared();
-    let h: f64 = dot(r.direction(), &oc);
-    let c: f64 = radius.mul_add(-radius, oc.len_squared());
-
-    let discriminant: f64 = h.mul_add(h, -(a * c));
-    if discriminant < 0.0 {
-        return false;
-    }
+        let discriminant: f64 = h.mul_add(h, -(a * c));
+        if discriminant < 0.0 {
+            return false;
+        }
 
-    let sqrtd: f64 = discriminant.sqrt();
+        let sqrtd: f64 = discriminant.sqrt();
 
-    // Find nearest root that lies in the acceptable range
-    let mut root: f64 = (h - sqrtd) / a;
-    if !ray_t.surrounds(root) {
-        root = (h + sqrtd) / a;
+        // Find nearest root that lies in the acceptable range
+        let mut root: f64 = (h - sqrtd) / a;
         if !ray_t.surrounds(root) {
-            return false;
+            root = (h + sqrtd) / a;
+            if !ray_t.surrounds(root) {
+                return false;
+            }
         }
+
+        rec.t = root;
+        rec.p = r.at(rec.t);
+        let outward_normal = (rec.p - self.center) / self.radius;
+        rec.set_face_normal(r, outward_normal);
+        (rec.u, rec.v) = sphere_uv(&outward_normal);
+        rec.mat = &self.mat;
+        true
     }
 
-    rec.t = root;
-    rec.p = r.at(rec.t);
-    let outward_normal = (rec.p - center) / radius;
-    rec.set_face_normal(r, outward_normal);
-    rec.mat = mat;
-    true
+    fn bounding_box(&self) -> Aabb {
+        let r = self.radius.abs();
+        let rvec = Vec3::new(r, r, r);
+        Aabb::from_points(&(self.center - rvec), &(self.center + rvec))
+    }
+}
+
+/// Surface coordinates of a point on the unit sphere.
+/// u runs around the y axis starting from -x, v runs from the bottom pole to the top
+fn sphere_uv(p: &Point3) -> (f64, f64) {
+    let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z()).atan2(p.x()) + PI;
+    (phi / (2.0 * PI), theta / PI)
 }
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+
+use crate::{
+    color::Color,
+    convert::to_f64,
+    image::{Image, WrapMode},
+    perlin::Perlin,
+    vec3::Point3,
+};
+
+/// Color varying over a surface, looked up by the hit's surface coordinates and position
+#[derive(Clone)]
+pub enum Texture {
+    Solid(Color),
+    /// Alternating cubes of edge `scale` in world space
+    Checker {
+        scale: f64,
+        even: Arc<Self>,
+        odd: Arc<Self>,
+    },
+    /// Alternating cells over the surface coordinates, `width` by `height` cells
+    UvChecker {
+        width: f64,
+        height: f64,
+        even: Arc<Self>,
+        odd: Arc<Self>,
+    },
+    Image(ImageTexture),
+    Noise(NoiseTexture),
+}
+
+impl Texture {
+    #[must_use]
+    pub fn checker(scale: f64, even: impl Into<Self>, odd: impl Into<Self>) -> Self {
+        Self::Checker {
+            scale,
+            even: Arc::new(even.into()),
+            odd: Arc::new(odd.into()),
+        }
+    }
+
+    #[must_use]
+    pub fn uv_checker(width: f64, height: f64, even: impl Into<Self>, odd: impl Into<Self>) -> Self {
+        Self::UvChecker {
+            width,
+            height,
+            even: Arc::new(even.into()),
+            odd: Arc::new(odd.into()),
+        }
+    }
+
+    #[must_use]
+    pub fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Checker { scale, even, odd } => {
+                let cell = |x: f64| (x / scale).floor();
+                let sum = cell(p.x()) + cell(p.y()) + cell(p.z());
+                if sum.rem_euclid(2.0) < 1.0 {
+                    even.value(u, v, p)
+                } else {
+                    odd.value(u, v, p)
+                }
+            }
+            Self::UvChecker {
+                width,
+                height,
+                even,
+                odd,
+            } => {
+                let sum = (u * width).floor() + (v * height).floor();
+                if sum.rem_euclid(2.0) < 1.0 {
+                    even.value(u, v, p)
+                } else {
+                    odd.value(u, v, p)
+                }
+            }
+            Self::Image(texture) => texture.value(u, v),
+            Self::Noise(texture) => texture.value(p),
+        }
+    }
+}
+
+impl From<Color> for Texture {
+    fn from(color: Color) -> Self {
+        Self::Solid(color)
+    }
+}
+
+impl From<ImageTexture> for Texture {
+    fn from(texture: ImageTexture) -> Self {
+        Self::Image(texture)
+    }
+}
+
+impl From<NoiseTexture> for Texture {
+    fn from(texture: NoiseTexture) -> Self {
+        Self::Noise(texture)
+    }
+}
+
+/// Procedural pattern built from Perlin noise
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoisePattern {
+    /// Plain smooth noise
+    #[default]
+    Smooth,
+    /// Seven octave turbulence
+    Turbulence,
+    /// Sine stripes along z, warped by turbulence
+    Marble,
+    /// Rings around the y axis, warped by turbulence
+    Wood,
+    /// Six octaves of fractal Brownian motion
+    Fbm,
+}
+
+/// Blend between `low` and `high` driven by a noise pattern sampled at the hit position
+#[derive(Clone)]
+pub struct NoiseTexture {
+    pub noise: Arc<Perlin>,
+    pub pattern: NoisePattern,
+    pub scale: f64, // Frequency of the pattern in world space
+    pub low: Color,
+    pub high: Color,
+}
+
+impl NoiseTexture {
+    /// Black to white pattern from a fresh noise generator seeded with `seed`
+    #[must_use]
+    pub fn new(seed: u64, pattern: NoisePattern, scale: f64) -> Self {
+        Self {
+            noise: Arc::new(Perlin::new(seed)),
+            pattern,
+            scale,
+            low: Color::new(0.0, 0.0, 0.0),
+            high: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    #[must_use]
+    pub fn value(&self, p: &Point3) -> Color {
+        let sp = self.scale * p;
+        let t = match self.pattern {
+            NoisePattern::Smooth => f64::midpoint(1.0, self.noise.noise(&sp)),
+            NoisePattern::Turbulence => self.noise.turbulence(&sp, 7),
+            NoisePattern::Marble => {
+                f64::midpoint(1.0, 10.0f64.mul_add(self.noise.turbulence(p, 7), sp.z()).sin())
+            }
+            NoisePattern::Wood => {
+                let rings = 0.5f64.mul_add(self.noise.turbulence(&sp, 4), sp.x().hypot(sp.z()));
+                (rings * 4.0).fract()
+            }
+            NoisePattern::Fbm => f64::midpoint(1.0, self.noise.fbm(&sp, 6, 2.0, 0.5)),
+        };
+        let t = t.clamp(0.0, 1.0);
+        (1.0 - t) * self.low + t * self.high
+    }
+}
+
+/// Image mapped onto the surface coordinates, with v = 0 at the bottom row
+#[derive(Clone)]
+pub struct ImageTexture {
+    pub image: Arc<Image>,
+    pub wrap_u: WrapMode,
+    pub wrap_v: WrapMode,
+    pub bilinear: bool, // Blend the four nearest pixels instead of taking the nearest one
+}
+
+impl ImageTexture {
+    /// Bilinearly filtered texture repeating in both directions
+    #[must_use]
+    pub const fn new(image: Arc<Image>) -> Self {
+        Self {
+            image,
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Repeat,
+            bilinear: true,
+        }
+    }
+
+    #[must_use]
+    pub fn value(&self, u: f64, v: f64) -> Color {
+        let x = u * to_f64(self.image.width());
+        let y = (1.0 - v) * to_f64(self.image.height());
+        if self.bilinear {
+            self.image.bilinear(x, y, self.wrap_u, self.wrap_v)
+        } else {
+            self.image.nearest(x, y, self.wrap_u, self.wrap_v)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(value: f64) -> Color {
+        Color::new(value, value, value)
+    }
+
+    /// Texture over an image of the given grey values, `width` pixels per row
+    fn image_texture(width: usize, values: &[f64], wrap: WrapMode, bilinear: bool) -> ImageTexture {
+        let pixels = values.iter().map(|&value| grey(value)).collect();
+        let image = Image::from_pixels(width, values.len() / width, pixels).expect("whole rows of pixels");
+        ImageTexture {
+            image: Arc::new(image),
+            wrap_u: wrap,
+            wrap_v: wrap,
+            bilinear,
+        }
+    }
+
+    #[test]
+    fn checker_alternates_between_cells() {
+        let checker = Texture::checker(2.0, grey(0.0), grey(1.0));
+        let cases = [
+            (Point3::new(0.5, 0.5, 0.5), 0.0),
+            (Point3::new(2.5, 0.5, 0.5), 1.0),
+            (Point3::new(2.5, 2.5, 0.5), 0.0),
+            (Point3::new(-0.5, 0.5, 0.5), 1.0),
+            (Point3::new(-0.5, -0.5, 0.5), 0.0),
+            (Point3::new(-0.5, -0.5, -0.5), 1.0),
+        ];
+        for (p, expected) in cases {
+            assert_eq!(checker.value(0.0, 0.0, &p).e, [expected; 3], "at {:?}", p.e);
+        }
+    }
+
+    #[test]
+    fn uv_checker_alternates_between_cells() {
+        let checker = Texture::uv_checker(4.0, 2.0, grey(0.0), grey(1.0));
+        let p = Point3::default();
+        assert_eq!(checker.value(0.1, 0.1, &p).e, [0.0; 3]);
+        assert_eq!(checker.value(0.3, 0.1, &p).e, [1.0; 3]);
+        assert_eq!(checker.value(0.3, 0.6, &p).e, [0.0; 3]);
+        assert_eq!(checker.value(0.9, 0.4, &p).e, [1.0; 3]);
+        assert_eq!(checker.value(0.9, 0.9, &p).e, [0.0; 3]);
+    }
+
+    #[test]
+    fn image_lookup_is_bilinear() {
+        let texture = image_texture(2, &[0.0, 1.0, 2.0, 3.0], WrapMode::Clamp, true);
+        // Pixel centers sit at u and v of 0.25 and 0.75, with v = 0 at the bottom row
+        assert_eq!(texture.value(0.25, 0.75).e, [0.0; 3]);
+        assert_eq!(texture.value(0.75, 0.25).e, [3.0; 3]);
+        assert_eq!(texture.value(0.5, 0.75).e, [0.5; 3]);
+        assert_eq!(texture.value(0.25, 0.5).e, [1.0; 3]);
+        assert_eq!(texture.value(0.5, 0.5).e, [1.5; 3]);
+    }
+
+    #[test]
+    fn image_lookup_without_filtering_takes_nearest() {
+        let texture = image_texture(2, &[0.0, 1.0, 2.0, 3.0], WrapMode::Clamp, false);
+        assert_eq!(texture.value(0.45, 0.55).e, [0.0; 3]);
+        assert_eq!(texture.value(0.55, 0.45).e, [3.0; 3]);
+    }
+
+    #[test]
+    fn wrap_modes_outside_unit_range() {
+        // Nearest lookups on a row of four, u = -0.25 is pixel -1 and u = 1.25 is pixel 5
+        let cases = [(WrapMode::Repeat, [3.0, 1.0]), (WrapMode::Clamp, [0.0, 3.0]), (WrapMode::Mirror, [0.0, 2.0])];
+        for (wrap, [below, above]) in cases {
+            let texture = image_texture(4, &[0.0, 1.0, 2.0, 3.0], wrap, false);
+            assert_eq!(texture.value(-0.25, 0.5).e, [below; 3], "{wrap:?} at u = -0.25");
+            assert_eq!(texture.value(1.25, 0.5).e, [above; 3], "{wrap:?} at u = 1.25");
+        }
+    }
+
+    #[test]
+    fn noise_texture_stays_between_low_and_high() {
+        let patterns = [
+            NoisePattern::Smooth,
+            NoisePattern::Turbulence,
+            NoisePattern::Marble,
+            NoisePattern::Wood,
+            NoisePattern::Fbm,
+        ];
+        for pattern in patterns {
+            let texture = NoiseTexture::new(3, pattern, 4.0);
+            for k in 0..100 {
+                let t = to_f64(k);
+                let c = texture.value(&Point3::new(0.37 * t, -0.21 * t, 0.13 * t));
+                assert!(c.e.iter().all(|x| (0.0..=1.0).contains(x)), "{pattern:?} gives {:?}", c.e);
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::VecDeque,
+    sync::{Condvar, Mutex},
+};
+
+use crate::convert::to_f64;
+
+/// Order tiles are handed out in, which is also roughly the order they finish in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TileOrder {
+    Scanline, // Rows of tiles from the top left
+    Spiral,   // Rings of tiles outward from the center, so the subject shows up first
+    #[default]
+    Hilbert, // Along a Hilbert curve, keeping consecutive tiles close together for cache locality
+}
+
+/// Rectangle of pixels `x0..x1` by `y0..y1`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Tile {
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    #[must_use]
+    pub const fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    #[must_use]
+    pub const fn pixel_count(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    /// Tile grown by `border` pixels on every side, kept inside a `width` by `height` image
+    #[must_use]
+    pub fn expanded(&self, border: usize, width: usize, height: usize) -> Self {
+        Self {
+            x0: self.x0.saturating_sub(border),
+            y0: self.y0.saturating_sub(border),
+            x1: (self.x1 + border).min(width),
+            y1: (self.y1 + border).min(height),
+        }
+    }
+}
+
+/// Splits a `width` by `height` image into tiles of at most `size` pixels square, in `order`
+///
+/// # Panics
+///
+/// Panics if `size` is zero
+#[must_use]
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    assert!(size > 0, "tile size must be positive");
+    let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+    let mut grid: Vec<(usize, usize)> = (0..rows).flat_map(|ty| (0..columns).map(move |tx| (tx, ty))).collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let center_x = to_f64(columns.saturating_sub(1)) / 2.0;
+            let center_y = to_f64(rows.saturating_sub(1)) / 2.0;
+            let ring_and_angle = |&(tx, ty): &(usize, usize)| {
+                let (dx, dy) = (to_f64(tx) - center_x, to_f64(ty) - center_y);
+                (dx.abs().max(dy.abs()).ceil(), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| {
+                let ((ring_a, angle_a), (ring_b, angle_b)) = (ring_and_angle(a), ring_and_angle(b));
+                ring_a.total_cmp(&ring_b).then(angle_a.total_cmp(&angle_b))
+            });
+        }
+        TileOrder::Hilbert => {
+            let n = columns.max(rows).next_power_of_two();
+            grid.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+        }
+    }
+    grid.into_iter()
+        .map(|(tx, ty)| Tile {
+            x0: tx * size,
+            y0: ty * size,
+            x1: ((tx + 1) * size).min(width),
+            y1: ((ty + 1) * size).min(height),
+        })
+        .collect()
+}
+
+/// Distance along the Hilbert curve filling a `side` by `side` grid, `side` a power of two
+fn hilbert_index(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut distance = 0;
+    let mut half = side / 2;
+    while half > 0 {
+        let right = usize::from(x & half > 0);
+        let lower = usize::from(y & half > 0);
+        distance += half * half * ((3 * right) ^ lower);
+        // Rotate the quadrant so the curve inside it has the standard orientation
+        if lower == 0 {
+            if right == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            (x, y) = (y, x);
+        }
+        half /= 2;
+    }
+    distance
+}
+
+/// Tile handed to a worker, along with the rendering pass it belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileJob {
+    pub pass: usize,
+    pub tile: Tile,
+}
+
+/// Tiles shared out between a persistent set of workers
+///
+/// Each worker takes from the front of its own queue and steals from the back of the others
+/// once it runs dry. Workers that find every queue empty wait for the next pass until the
+/// queue is closed
+pub struct TileQueue {
+    queues: Vec<Mutex<VecDeque<TileJob>>>,
+    state: Mutex<QueueState>,
+    refilled: Condvar,
+}
+
+#[derive(Default)]
+struct QueueState {
+    generation: usize, // Bumped for every pass pushed
+    closed: bool,
+}
+
+impl TileQueue {
+    #[must_use]
+    pub fn new(workers: usize) -> Self {
+        Self {
+            queues: (0..workers.max(1)).map(|_| Mutex::new(VecDeque::new())).collect(),
+            state: Mutex::new(QueueState::default()),
+            refilled: Condvar::new(),
+        }
+    }
+
+    /// Deals the tiles of a pass round robin to the workers, so every worker starts near the
+    /// front of the overall order, and wakes any that are waiting
+    pub fn push_pass(&self, pass: usize, tiles: &[Tile]) {
+        let n = self.queues.len();
+        for (w, queue) in self.queues.iter().enumerate() {
+            if let Ok(mut queue) = queue.lock() {
+                queue.extend(tiles.iter().skip(w).step_by(n).map(|&tile| TileJob { pass, tile }));
+            }
+        }
+        if let Ok(mut state) = self.state.lock() {
+            state.generation += 1;
+        }
+        self.refilled.notify_all();
+    }
+
+    /// Releases every waiting worker, `next` returns `None` from now on
+    pub fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+        }
+        self.refilled.notify_all();
+    }
+
+    /// Next job for `worker`, waiting for another pass if every queue is empty.
+    /// `None` once the queue is closed
+    #[must_use]
+    pub fn next(&self, worker: usize) -> Option<TileJob> {
+        loop {
+            let generation = {
+                let state = self.state.lock().ok()?;
+                if state.closed {
+                    return None;
+                }
+                state.generation
+            };
+            if let Some(job) = self.take(worker) {
+                return Some(job);
+            }
+            let state = self.state.lock().ok()?;
+            drop(
+                self.refilled
+                    .wait_while(state, |s| !s.closed && s.generation == generation)
+                    .ok()?,
+            );
+        }
+    }
+
+    fn take(&self, worker: usize) -> Option<TileJob> {
+        let n = self.queues.len();
+        let own = worker % n;
+        if let Some(job) = self.queues[own].lock().ok().and_then(|mut q| q.pop_front()) {
+            return Some(job);
+        }
+        (1..n).find_map(|k| self.queues[(own + k) % n].lock().ok().and_then(|mut q| q.pop_back()))
+    }
+}
+
+/// Closes the queue when dropped, so waiting workers are released even if the thread
+/// feeding the queue panics
+pub struct CloseOnDrop<'a>(pub &'a TileQueue);
+
+impl Drop for CloseOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+
+    #[test]
+    fn every_order_covers_each_pixel_once() {
+        // Square and not, with and without partial tiles, with grids of 1x1 up to 7x3 tiles
+        for (width, height, size) in [(64, 64, 16), (100, 37, 16), (37, 100, 10), (5, 3, 8), (1, 1, 1), (70, 30, 10)] {
+            for order in ORDERS {
+                let tiles = tiles(width, height, size, order);
+                assert_eq!(tiles.len(), width.div_ceil(size) * height.div_ceil(size));
+                let mut covered = vec![0; width * height];
+                for tile in &tiles {
+                    assert!(tile.width() <= size && tile.height() <= size);
+                    for y in tile.y0..tile.y1 {
+                        for x in tile.x0..tile.x1 {
+                            covered[y * width + x] += 1;
+                        }
+                    }
+                }
+                assert!(covered.iter().all(|&n| n == 1), "{order:?} on {width}x{height} in tiles of {size}");
+            }
+        }
+    }
+
+    #[test]
+    fn hilbert_index_visits_neighbours_in_turn() {
+        let side = 8;
+        let mut cells: Vec<(usize, usize)> = (0..side).flat_map(|y| (0..side).map(move |x| (x, y))).collect();
+        cells.sort_by_key(|&(x, y)| hilbert_index(side, x, y));
+        let indices: Vec<usize> = cells.iter().map(|&(x, y)| hilbert_index(side, x, y)).collect();
+        assert_eq!(indices, (0..side * side).collect::<Vec<_>>());
+        for pair in cells.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            assert_eq!(x0.abs_diff(x1) + y0.abs_diff(y1), 1, "{pair:?}");
+        }
+    }
+
+    #[test]
+    fn spiral_starts_at_center() {
+        let tiles = tiles(50, 50, 10, TileOrder::Spiral);
+        assert_eq!(tiles[0], Tile { x0: 20, y0: 20, x1: 30, y1: 30 });
+    }
+
+    #[test]
+    fn queue_with_more_workers_than_tiles_drains_every_job() {
+        let workers = 8;
+        let tiles = tiles(30, 10, 10, TileOrder::Scanline);
+        let queue = TileQueue::new(workers);
+        let (sender, receiver) = mpsc::channel();
+        let mut jobs: Vec<TileJob> = thread::scope(|scope| {
+            for worker in 0..workers {
+                let (queue, sender) = (&queue, sender.clone());
+                scope.spawn(move || {
+                    while let Some(job) = queue.next(worker) {
+                        sender.send(job).expect("receiver outlives the workers");
+                    }
+                });
+            }
+            drop(sender);
+            let _close = CloseOnDrop(&queue);
+            let mut jobs = Vec::new();
+            for pass in 0..2 {
+                queue.push_pass(pass, &tiles);
+                jobs.extend(receiver.iter().take(tiles.len()));
+            }
+            jobs
+        });
+        jobs.sort_by_key(|job| (job.pass, job.tile.y0, job.tile.x0));
+        let expected: Vec<TileJob> = (0..2).flat_map(|pass| tiles.iter().map(move |&tile| TileJob { pass, tile })).collect();
+        assert_eq!(jobs, expected);
+    }
+
+    #[test]
+    fn idle_worker_steals_from_others() {
+        let queue = TileQueue::new(4);
+        let tiles = tiles(20, 10, 10, TileOrder::Scanline);
+        queue.push_pass(0, &tiles);
+        // Worker 3 was dealt nothing, so both jobs are stolen
+        let stolen = [queue.next(3), queue.next(3)];
+        assert!(stolen.iter().all(Option::is_some));
+        assert_ne!(stolen[0], stolen[1]);
+        queue.close();
+        assert_eq!(queue.next(0), None);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{color::{Color, linear_to_srgb, luminance}, convert::float_to_byte};
+
+/// Curve compressing scene radiance into the range a display can show
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneMap {
+    #[default]
+    Clamp,    // Leave values alone, clipping anything above 1
+    Reinhard, // Luminance scaled by 1 / (1 + luminance), approaching but never reaching 1
+    /// Reinhard adjusted to reach 1 at a luminance of `white`, so values up to it keep contrast
+    ExtendedReinhard { white: f64 },
+    AcesFitted, // Stephen Hill's fit of the ACES reference rendering and sRGB output transforms
+    AgX,        // Troy Sobotka's AgX, which desaturates highlights towards white instead of skewing hues
+}
+
+/// Steps from the linear radiance of a render to the values of an 8 bit display image:
+/// exposure, tone mapping, then the sRGB transfer function
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DisplayTransform {
+    pub exposure: f64, // Stops to brighten by before tone mapping, negative to darken
+    pub tone_map: ToneMap,
+}
+
+impl DisplayTransform {
+    /// Exposed and tone mapped color, still linear but within `[0, 1]`
+    #[must_use]
+    pub fn apply(&self, linear: &Color) -> Color {
+        let c = self.exposure.exp2() * linear;
+        let mapped = match self.tone_map {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => scale_luminance(&c, |l| l / (1.0 + l)),
+            ToneMap::ExtendedReinhard { white } => {
+                scale_luminance(&c, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMap::AcesFitted => aces_fitted(&c),
+            ToneMap::AgX => agx(&c),
+        };
+        Color {
+            e: mapped.e.map(|x| x.clamp(0.0, 1.0)),
+        }
+    }
+
+    /// sRGB encoded 8 bit value of each channel, with NaN saturating to 0 in the cast
+    #[must_use]
+    pub fn to_rgb8(&self, linear: &Color) -> [u8; 3] {
+        self.apply(linear).e.map(|c| float_to_byte((255.0 * linear_to_srgb(c)).round()))
+    }
+}
+
+/// Color scaled so its luminance becomes `curve` of the old one, keeping the hue
+fn scale_luminance(c: &Color, curve: impl Fn(f64) -> f64) -> Color {
+    let l = luminance(c);
+    if l <= 0.0 { Color::default() } else { (curve(l) / l) * c }
+}
+
+fn aces_fitted(c: &Color) -> Color {
+    const INPUT: [[f64; 3]; 3] = [
+        [0.597_19, 0.354_58, 0.048_23],
+        [0.076_00, 0.908_34, 0.015_66],
+        [0.028_40, 0.133_83, 0.837_77],
+    ];
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.604_75, -0.531_08, -0.073_67],
+        [-0.102_08, 1.108_13, -0.006_05],
+        [-0.003_27, -0.072_76, 1.076_02],
+    ];
+    // Reference rendering and output transforms fitted as one rational curve
+    let fit = |v: f64| {
+        let numerator = v.mul_add(v + 0.024_578_6, -0.000_090_537);
+        let denominator = v.mul_add(0.983_729f64.mul_add(v, 0.432_951), 0.238_081);
+        numerator / denominator
+    };
+    // The fit turns back up below zero, so negative inputs would map to bright values
+    let v = transform(&INPUT, c);
+    transform(&OUTPUT, &Color { e: v.e.map(|x| fit(x.max(0.0))) })
+}
+
+fn agx(c: &Color) -> Color {
+    const INSET: [[f64; 3]; 3] = [
+        [0.842_479_062_253_094, 0.078_433_599_999_999_2, 0.079_223_745_147_764_3],
+        [0.042_328_242_261_012_3, 0.878_468_636_469_772, 0.079_166_127_460_543_4],
+        [0.042_375_654_905_705_1, 0.078_433_6, 0.879_142_973_793_104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.196_879_005_120_17, -0.098_020_881_140_136_8, -0.099_029_744_079_720_5],
+        [-0.052_896_851_757_456_2, 1.151_903_129_904_17, -0.098_961_176_844_843_3],
+        [-0.052_971_635_514_443_8, -0.098_043_450_117_124_1, 1.151_073_672_641_16],
+    ];
+    const MIN_EV: f64 = -12.473_93;
+    const MAX_EV: f64 = 4.026_069;
+    // Polynomial fit of the base contrast curve over log encoded values
+    let contrast = |x: f64| {
+        let (x2, x4) = (x * x, x * x * x * x);
+        let poly = 15.5f64.mul_add(x4 * x2, (-40.14f64).mul_add(x4 * x, 31.96 * x4));
+        poly + (-6.868f64).mul_add(x2 * x, 0.4298f64.mul_add(x2, 0.1191f64.mul_add(x, -0.002_32)))
+    };
+    let log_encode = |v: f64| (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+    let v = transform(&INSET, c);
+    let display = transform(&OUTSET, &Color { e: v.e.map(|x| contrast(log_encode(x))) });
+    // The curve gives display encoded values, decoded here as the sRGB transfer function comes after
+    Color {
+        e: display.e.map(|x| x.max(0.0).powf(2.2)),
+    }
+}
+
+/// Matrix given row by row times a column vector
+fn transform(rows: &[[f64; 3]; 3], c: &Color) -> Color {
+    let row = |r: &[f64; 3]| r[2].mul_add(c.z(), r[0].mul_add(c.x(), r[1] * c.y()));
+    Color::new(row(&rows[0]), row(&rows[1]), row(&rows[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TONE_MAPS: [ToneMap; 5] = [
+        ToneMap::Clamp,
+        ToneMap::Reinhard,
+        ToneMap::ExtendedReinhard { white: 4.0 },
+        ToneMap::AcesFitted,
+        ToneMap::AgX,
+    ];
+
+    fn grey(x: f64) -> Color {
+        Color::new(x, x, x)
+    }
+
+    fn transform(tone_map: ToneMap) -> DisplayTransform {
+        DisplayTransform { exposure: 0.0, tone_map }
+    }
+
+    #[test]
+    fn default_is_plain_srgb() {
+        let display = DisplayTransform::default();
+        assert_eq!(display.to_rgb8(&grey(0.18)), [118; 3]);
+        assert_eq!(display.to_rgb8(&Color::new(0.0, 0.5, 1.0)), [0, 188, 255]);
+    }
+
+    #[test]
+    fn to_rgb8_clamps_out_of_range_values() {
+        for tone_map in TONE_MAPS {
+            let display = transform(tone_map);
+            assert_eq!(display.to_rgb8(&grey(f64::NAN)), [0; 3], "{tone_map:?}");
+            assert_eq!(display.to_rgb8(&grey(-3.0)), [0; 3], "{tone_map:?}");
+            let bright = display.to_rgb8(&grey(1e6));
+            assert!(bright.iter().all(|&c| c > 200), "{tone_map:?} gives {bright:?}");
+        }
+        assert_eq!(transform(ToneMap::Clamp).to_rgb8(&grey(f64::INFINITY)), [255; 3]);
+    }
+
+    #[test]
+    fn exposure_doubles_per_stop() {
+        let display = DisplayTransform {
+            exposure: 1.0,
+            tone_map: ToneMap::Clamp,
+        };
+        assert!((display.apply(&grey(0.2)).x() - 0.4).abs() < 1e-12);
+    }
+
+    #[test]
+    fn tone_maps_keep_black() {
+        for tone_map in TONE_MAPS {
+            let black = transform(tone_map).apply(&grey(0.0));
+            assert!(black.e.iter().all(|c| c.abs() < 1e-6), "{tone_map:?} gives {:?}", black.e);
+        }
+    }
+
+    #[test]
+    fn tone_maps_increase_within_unit_range() {
+        for tone_map in TONE_MAPS {
+            let display = transform(tone_map);
+            let values: Vec<f64> = (0..=100).map(|k| display.apply(&grey(f64::from(k) / 100.0)).y()).collect();
+            assert!(values.windows(2).all(|w| w[1] >= w[0]), "{tone_map:?} decreases");
+            assert!(values[100] > values[10] && values[10] > values[0], "{tone_map:?} is flat");
+        }
+    }
+
+    #[test]
+    fn extended_reinhard_reaches_one_at_white() {
+        let display = transform(ToneMap::ExtendedReinhard { white: 4.0 });
+        assert!((display.apply(&grey(4.0)).y() - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, HitRecord},
+    internal::Interval,
+    material::Mat,
+    ray::Ray,
+    vec3::{
+        Point3,
+        Vec3,
+        cross,
+        dot,
+        unit_vector,
+    },
+};
+
+/// Single flat shaded triangle, front face is the side the vertices wind counterclockwise
+#[derive(Clone)]
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    pub mat: Mat,
+}
+
+impl Triangle {
+    #[must_use]
+    pub const fn new(p0: Point3, p1: Point3, p2: Point3, mat: Mat) -> Self {
+        Self {
+            vertices: [p0, p1, p2],
+            mat,
+        }
+    }
+}
+
+impl Hit for Triangle {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: &Interval, rec: &mut HitRecord<'a>) -> bool {
+        let [p0, p1, p2] = &self.vertices;
+        let Some((t, b1, b2)) = intersect(r, ray_t, p0, p1, p2) else {
+            return false;
+        };
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.u = b1;
+        rec.v = b2;
+        rec.mat = &self.mat;
+        rec.set_face_normal(r, geometric_normal(p0, p1, p2));
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        triangle_bounds(&self.vertices[0], &self.vertices[1], &self.vertices[2])
+    }
+}
+
+/// Moller-Trumbore ray triangle intersection.
+/// Returns the ray parameter and the barycentric weights of `p1` and `p2` at the hit
+#[must_use]
+pub fn intersect(
+    r: &Ray,
+    ray_t: &Interval,
+    p0: &Point3,
+    p1: &Point3,
+    p2: &Point3,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = cross(r.direction(), &edge2);
+    let det = dot(&edge1, &pvec);
+    // Ray is parallel to the triangle plane
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - p0;
+    let b1 = dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = cross(&tvec, &edge1);
+    let b2 = dot(r.direction(), &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = dot(&edge2, &qvec) * inv_det;
+    ray_t.surrounds(t).then_some((t, b1, b2))
+}
+
+#[must_use]
+pub fn geometric_normal(p0: &Point3, p1: &Point3, p2: &Point3) -> Vec3 {
+    unit_vector(&cross(&(p1 - p0), &(p2 - p0)))
+}
+
+pub(crate) fn triangle_bounds(p0: &Point3, p1: &Point3, p2: &Point3) -> Aabb {
+    Aabb::surrounding(&Aabb::from_points(p0, p1), &Aabb::from_points(p2, p2)).pad_to_minimums()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertain, texture::Texture};
+
+    /// Right triangle in the z = 0 plane, wound counterclockwise seen from +z
+    fn corner_triangle() -> Triangle {
+        let mat = Mat::Lambertain(Lambertain {
+            albedo: Texture::Solid(Color::new(0.5, 0.5, 0.5)),
+        });
+        Triangle::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), mat)
+    }
+
+    /// Hit of a ray going straight down onto the z = 0 plane at `(x, y)`
+    fn hit_from_above(triangle: &Triangle, x: f64, y: f64) -> Option<HitRecord<'_>> {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(&Point3::new(x, y, 1.0), &Vec3::new(0.0, 0.0, -1.0));
+        triangle.hit(&r, &Interval::new(0.001, &f64::INFINITY), &mut rec).then_some(rec)
+    }
+
+    #[test]
+    fn hits_inside() {
+        let triangle = corner_triangle();
+        let rec = hit_from_above(&triangle, 0.25, 0.25).expect("ray hits the triangle");
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.p - Point3::new(0.25, 0.25, 0.0)).len() < 1e-12);
+    }
+
+    #[test]
+    fn misses_outside() {
+        let triangle = corner_triangle();
+        for (x, y) in [(0.6, 0.6), (-0.1, 0.5), (0.5, -0.1), (2.0, 2.0)] {
+            assert!(hit_from_above(&triangle, x, y).is_none(), "hit at ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn hits_on_edges_and_vertices() {
+        let triangle = corner_triangle();
+        for (x, y) in [(0.5, 0.0), (0.0, 0.5), (0.5, 0.5), (0.0, 0.0), (1.0, 0.0), (0.0, 1.0)] {
+            assert!(hit_from_above(&triangle, x, y).is_some(), "missed ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn misses_parallel_ray() {
+        let r = Ray::new(&Point3::new(-1.0, 0.25, 0.0), &Vec3::new(1.0, 0.0, 0.0));
+        let [p0, p1, p2] = corner_triangle().vertices;
+        assert_eq!(intersect(&r, &Interval::new(0.001, &f64::INFINITY), &p0, &p1, &p2), None);
+    }
+
+    #[test]
+    fn uv_is_barycentric() {
+        // With p1 and p2 one unit along x and y, their barycentric weights are the coordinates
+        let triangle = corner_triangle();
+        for (x, y) in [(0.1, 0.2), (0.5, 0.25), (0.0, 1.0)] {
+            let rec = hit_from_above(&triangle, x, y).expect("ray hits the triangle");
+            assert!((rec.u - x).abs() < 1e-12 && (rec.v - y).abs() < 1e-12, "({x}, {y}) gives ({}, {})", rec.u, rec.v);
+        }
+    }
+
+    #[test]
+    fn front_face_follows_winding() {
+        let triangle = corner_triangle();
+        let front = hit_from_above(&triangle, 0.25, 0.25).expect("hit from the front");
+        assert!(front.front_face);
+        assert_eq!(front.normal.e, [0.0, 0.0, 1.0]);
+
+        let mut flipped = corner_triangle();
+        flipped.vertices.swap(1, 2);
+        let back = hit_from_above(&flipped, 0.25, 0.25).expect("hit from the back");
+        assert!(!back.front_face);
+        assert_eq!(back.normal.e, [0.0, 0.0, 1.0]);
+    }
+}
@@ -1,3 +1,5 @@
+use rand::Rng;
+
 use crate::{
     rand_f64,
     rand_range_f64,
@@ -40,18 +42,18 @@ impl Vec3 {
     }
 
     #[must_use]
-    pub fn random() -> Self {
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
         Self {
-            e: [rand_f64(), rand_f64(), rand_f64()],
+            e: [rand_f64(rng), rand_f64(rng), rand_f64(rng)],
         }
     }
     #[must_use]
-    pub fn random_rng(min: f64, max: f64) -> Self {
+    pub fn random_rng<R: Rng + ?Sized>(rng: &mut R, min: f64, max: f64) -> Self {
         Self {
             e: [
-                rand_range_f64(min, max),
-                rand_range_f64(min, max),
-                rand_range_f64(min, max),
+                rand_range_f64(rng, min, max),
+                rand_range_f64(rng, min, max),
+                rand_range_f64(rng, min, max),
             ],
         }
     }
@@ -368,9 +370,9 @@ pub fn unit_vector(v: &Vec3) -> Vec3 {
 
 #[inline]
 #[must_use]
-pub fn random_unit_vector() -> Vec3 {
+pub fn random_unit_vector<R: Rng + ?Sized>(rng: &mut R) -> Vec3 {
     loop {
-        let p = Vec3::random_rng(-1_f64, 1_f64);
+        let p = Vec3::random_rng(rng, -1_f64, 1_f64);
         let lensq = p.len_squared();
         if lensq <= 1_f64 {
             return p / lensq.sqrt();
@@ -380,19 +382,48 @@ pub fn random_unit_vector() -> Vec3 {
 
 #[inline]
 #[must_use]
-pub fn random_in_unit_disk() -> Vec3 {
+pub fn random_in_unit_disk<R: Rng + ?Sized>(rng: &mut R) -> Vec3 {
     loop {
-        let p = Vec3::new(rand_range_f64(-1.0, 1.0), rand_range_f64(-1.0, 1.0), 0.0);
+        let p = Vec3::new(rand_range_f64(rng, -1.0, 1.0), rand_range_f64(rng, -1.0, 1.0), 0.0);
         if p.len_squared() < 1.0 {
             return p;
         }
     }
 }
 
+/// Point on the unit sphere for a point `(u, v)` of the unit square, mapping uniform points
+/// to uniform directions
+#[inline]
+#[must_use]
+pub fn sample_unit_vector((u, v): (f64, f64)) -> Vec3 {
+    let z = 2.0f64.mul_add(-u, 1.0);
+    let r = z.mul_add(-z, 1.0).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * v;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Point in the unit disk for a point `(u, v)` of the unit square, with Shirley and Chiu's
+/// concentric mapping, which keeps strata of the square compact in the disk
+#[inline]
+#[must_use]
+pub fn sample_unit_disk((u, v): (f64, f64)) -> Vec3 {
+    let (x, y) = (2.0f64.mul_add(u, -1.0), 2.0f64.mul_add(v, -1.0));
+    if x == 0.0 && y == 0.0 {
+        return Vec3::default();
+    }
+    let quarter = std::f64::consts::FRAC_PI_4;
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, quarter * (y / x))
+    } else {
+        (y, 2.0f64.mul_add(quarter, -quarter * (x / y)))
+    };
+    Vec3::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+}
+
 #[inline]
 #[must_use]
-pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
-    let on_unit_sphere = random_unit_vector();
+pub fn random_on_hemisphere<R: Rng + ?Sized>(rng: &mut R, normal: &Vec3) -> Vec3 {
+    let on_unit_sphere = random_unit_vector(rng);
     if dot(&on_unit_sphere, normal) > 0.0 {
         on_unit_sphere
     } else {
@@ -0,0 +1,568 @@
+use crate::convert::{to_u32, to_u8};
+
+const WINDOW_SIZE: usize = 32768; // Farthest back a match may reach
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+const MAX_CHAIN: usize = 128; // Candidates tried per position before settling for the best so far
+const BLOCK_TOKENS: usize = 1 << 15; // Tokens gathered before a block is emitted
+const MAX_CODE_LENGTH: usize = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: usize = 7;
+const END_OF_BLOCK: usize = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// Order the code length code lengths are stored in, rarely used ones last so they can be trimmed
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// Compresses `data` into a zlib stream (RFC 1950) holding deflate blocks (RFC 1951)
+#[must_use]
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter::default();
+    out.bytes.extend_from_slice(&[0x78, 0x9c]);
+    deflate(data, &mut out);
+    let mut bytes = out.finish();
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
+
+/// Adler-32 checksum used to close a zlib stream
+#[must_use]
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    const CHUNK: usize = 5552; // Largest run that cannot overflow before reducing
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for chunk in data.chunks(CHUNK) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// CRC-32 (ISO 3309) as used by PNG chunks
+#[must_use]
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0_u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0_u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+#[derive(Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { length: usize, distance: usize },
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Appends the low `count` bits of `value`, least significant bit first
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= u64::from(value) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.bits.to_le_bytes()[0]);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write(0, 8 - self.count);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+/// Greedy LZ77 with one step of lazy matching, emitted as a series of blocks that each use
+/// whichever of stored, fixed or dynamic Huffman coding is smallest
+fn deflate(data: &[u8], out: &mut BitWriter) {
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+    let mut tokens = Vec::with_capacity(BLOCK_TOKENS);
+    let mut block_start = 0;
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best = longest_match(data, pos, &head, &prev);
+        insert_hash(data, pos, &mut head, &mut prev);
+        if let Some((length, _)) = best
+            && length < MAX_MATCH
+            && pos + 1 < data.len()
+            && let Some((next_length, _)) = longest_match(data, pos + 1, &head, &prev)
+            && next_length > length
+        {
+            best = None;
+        }
+        if let Some((length, distance)) = best {
+            tokens.push(Token::Match { length, distance });
+            for p in pos + 1..pos + length {
+                insert_hash(data, p, &mut head, &mut prev);
+            }
+            pos += length;
+        } else {
+            tokens.push(Token::Literal(data[pos]));
+            pos += 1;
+        }
+        if tokens.len() >= BLOCK_TOKENS {
+            write_block(out, &tokens, &data[block_start..pos], false);
+            tokens.clear();
+            block_start = pos;
+        }
+    }
+    write_block(out, &tokens, &data[block_start..], true);
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let key = (usize::from(data[pos]) << 16) | (usize::from(data[pos + 1]) << 8) | usize::from(data[pos + 2]);
+    (key.wrapping_mul(0x9e37_79b1) >> 16) & ((1 << HASH_BITS) - 1)
+}
+
+fn insert_hash(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if pos + MIN_MATCH <= data.len() {
+        let h = hash(data, pos);
+        prev[pos] = head[h];
+        head[h] = pos;
+    }
+}
+
+/// Longest earlier occurrence of the bytes at `pos`, as `(length, distance)`
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> Option<(usize, usize)> {
+    if pos + MIN_MATCH > data.len() {
+        return None;
+    }
+    let max_length = MAX_MATCH.min(data.len() - pos);
+    let mut best: Option<(usize, usize)> = None;
+    let mut candidate = head[hash(data, pos)];
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || pos - candidate > WINDOW_SIZE {
+            break;
+        }
+        let length = data[candidate..]
+            .iter()
+            .zip(&data[pos..pos + max_length])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if length >= MIN_MATCH && best.is_none_or(|(l, _)| length > l) {
+            best = Some((length, pos - candidate));
+            if length == max_length {
+                break;
+            }
+        }
+        candidate = prev[candidate];
+    }
+    best
+}
+
+fn write_block(out: &mut BitWriter, tokens: &[Token], raw: &[u8], last: bool) {
+    let mut lit_freq = [0_usize; 286];
+    let mut dist_freq = [0_usize; 30];
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => lit_freq[usize::from(byte)] += 1,
+            Token::Match { length, distance } => {
+                lit_freq[257 + length_code(length)] += 1;
+                dist_freq[distance_code(distance)] += 1;
+            }
+        }
+    }
+    lit_freq[END_OF_BLOCK] += 1;
+
+    let lit_lengths = code_lengths(&lit_freq, MAX_CODE_LENGTH);
+    let dist_lengths = code_lengths(&dist_freq, MAX_CODE_LENGTH);
+    let header = DynamicHeader::new(&lit_lengths, &dist_lengths);
+    let (fixed_lit, fixed_dist) = fixed_lengths();
+
+    let dynamic_bits = header.cost() + symbol_cost(&lit_freq, &lit_lengths, &dist_freq, &dist_lengths);
+    let fixed_bits = symbol_cost(&lit_freq, &fixed_lit, &dist_freq, &fixed_dist);
+    let stored_bits = raw.len().div_ceil(0xffff).max(1) * 40 + raw.len() * 8;
+
+    if stored_bits <= fixed_bits.min(dynamic_bits) {
+        write_stored(out, raw, last);
+    } else if fixed_bits <= dynamic_bits {
+        out.write(u32::from(last), 1);
+        out.write(1, 2);
+        write_tokens(out, tokens, &fixed_lit, &fixed_dist);
+    } else {
+        out.write(u32::from(last), 1);
+        out.write(2, 2);
+        header.write(out);
+        write_tokens(out, tokens, &lit_lengths, &dist_lengths);
+    }
+}
+
+fn write_stored(out: &mut BitWriter, raw: &[u8], last: bool) {
+    let mut chunks = raw.chunks(0xffff).peekable();
+    if chunks.peek().is_none() {
+        out.write(u32::from(last), 1);
+        out.write(0, 2);
+        out.align();
+        out.bytes.extend_from_slice(&[0, 0, 0xff, 0xff]);
+        return;
+    }
+    while let Some(chunk) = chunks.next() {
+        out.write(u32::from(last && chunks.peek().is_none()), 1);
+        out.write(0, 2);
+        out.align();
+        let len = u16::try_from(chunk.len()).unwrap_or(u16::MAX);
+        out.bytes.extend_from_slice(&len.to_le_bytes());
+        out.bytes.extend_from_slice(&(!len).to_le_bytes());
+        out.bytes.extend_from_slice(chunk);
+    }
+}
+
+fn write_tokens(out: &mut BitWriter, tokens: &[Token], lit_lengths: &[u8], dist_lengths: &[u8]) {
+    let lit_codes = canonical_codes(lit_lengths);
+    let dist_codes = canonical_codes(dist_lengths);
+    let symbol = |out: &mut BitWriter, codes: &[u32], lengths: &[u8], s: usize| {
+        out.write(codes[s], u32::from(lengths[s]));
+    };
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => symbol(out, &lit_codes, lit_lengths, usize::from(byte)),
+            Token::Match { length, distance } => {
+                let lc = length_code(length);
+                symbol(out, &lit_codes, lit_lengths, 257 + lc);
+                out.write(to_u32(length - usize::from(LENGTH_BASE[lc])), u32::from(LENGTH_EXTRA[lc]));
+                let dc = distance_code(distance);
+                symbol(out, &dist_codes, dist_lengths, dc);
+                out.write(to_u32(distance - usize::from(DISTANCE_BASE[dc])), u32::from(DISTANCE_EXTRA[dc]));
+            }
+        }
+    }
+    symbol(out, &lit_codes, lit_lengths, END_OF_BLOCK);
+}
+
+fn symbol_cost(lit_freq: &[usize], lit_lengths: &[u8], dist_freq: &[usize], dist_lengths: &[u8]) -> usize {
+    let lit: usize = lit_freq
+        .iter()
+        .zip(lit_lengths)
+        .enumerate()
+        .map(|(s, (&f, &l))| {
+            let extra = if s > END_OF_BLOCK { LENGTH_EXTRA[s - 257] } else { 0 };
+            f * usize::from(l + extra)
+        })
+        .sum();
+    let dist: usize = dist_freq
+        .iter()
+        .zip(dist_lengths)
+        .zip(DISTANCE_EXTRA)
+        .map(|((&f, &l), extra)| f * usize::from(l + extra))
+        .sum();
+    3 + lit + dist
+}
+
+/// Code lengths and their run length encoding for a dynamic Huffman block header
+struct DynamicHeader {
+    hlit: usize,
+    hdist: usize,
+    hclen: usize,
+    runs: Vec<(u8, u8)>, // Code length symbol and its extra bits value
+    cl_lengths: [u8; 19],
+}
+
+impl DynamicHeader {
+    fn new(lit_lengths: &[u8], dist_lengths: &[u8]) -> Self {
+        let hlit = trimmed_len(lit_lengths, 257);
+        let hdist = trimmed_len(dist_lengths, 1);
+        let all: Vec<u8> = lit_lengths[..hlit].iter().chain(&dist_lengths[..hdist]).copied().collect();
+        let runs = run_length_encode(&all);
+        let mut cl_freq = [0_usize; 19];
+        for &(s, _) in &runs {
+            cl_freq[usize::from(s)] += 1;
+        }
+        let cl = code_lengths(&cl_freq, MAX_CODE_LENGTH_CODE_LENGTH);
+        let mut cl_lengths = [0_u8; 19];
+        cl_lengths.copy_from_slice(&cl);
+        let hclen = CODE_LENGTH_ORDER
+            .iter()
+            .rposition(|&s| cl_lengths[s] != 0)
+            .map_or(4, |i| (i + 1).max(4));
+        Self {
+            hlit,
+            hdist,
+            hclen,
+            runs,
+            cl_lengths,
+        }
+    }
+
+    fn cost(&self) -> usize {
+        let runs: usize = self
+            .runs
+            .iter()
+            .map(|&(s, _)| usize::from(self.cl_lengths[usize::from(s)]) + run_extra_bits(s))
+            .sum();
+        14 + 3 * self.hclen + runs
+    }
+
+    fn write(&self, out: &mut BitWriter) {
+        out.write(to_u32(self.hlit - 257), 5);
+        out.write(to_u32(self.hdist - 1), 5);
+        out.write(to_u32(self.hclen - 4), 4);
+        for &s in &CODE_LENGTH_ORDER[..self.hclen] {
+            out.write(u32::from(self.cl_lengths[s]), 3);
+        }
+        let codes = canonical_codes(&self.cl_lengths);
+        for &(s, extra) in &self.runs {
+            let s_index = usize::from(s);
+            out.write(codes[s_index], u32::from(self.cl_lengths[s_index]));
+            out.write(u32::from(extra), to_u32(run_extra_bits(s)));
+        }
+    }
+}
+
+const fn run_extra_bits(symbol: u8) -> usize {
+    match symbol {
+        16 => 2,
+        17 => 3,
+        18 => 7,
+        _ => 0,
+    }
+}
+
+fn trimmed_len(lengths: &[u8], min: usize) -> usize {
+    lengths.iter().rposition(|&l| l != 0).map_or(min, |i| (i + 1).max(min))
+}
+
+/// Encodes code lengths with the repeat symbols 16 (previous length), 17 and 18 (zeros)
+fn run_length_encode(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let value = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == value).count();
+        let mut remaining = run;
+        if value == 0 {
+            while remaining >= 11 {
+                let n = remaining.min(138);
+                runs.push((18, to_u8(n - 11)));
+                remaining -= n;
+            }
+            if remaining >= 3 {
+                runs.push((17, to_u8(remaining - 3)));
+                remaining = 0;
+            }
+        } else {
+            runs.push((value, 0));
+            remaining -= 1;
+            while remaining >= 3 {
+                let n = remaining.min(6);
+                runs.push((16, to_u8(n - 3)));
+                remaining -= n;
+            }
+        }
+        runs.extend(std::iter::repeat_n((value, 0), remaining));
+        i += run;
+    }
+    runs
+}
+
+/// Huffman code lengths no longer than `max_length` for the given symbol frequencies.
+/// At least two symbols always get a code, as some decoders reject a single code
+fn code_lengths(freq: &[usize], max_length: usize) -> Vec<u8> {
+    let mut freq = freq.to_vec();
+    for _ in freq.iter().filter(|&&f| f > 0).count()..2 {
+        if let Some(f) = freq.iter_mut().find(|f| **f == 0) {
+            *f = 1;
+        }
+    }
+    let mut symbols: Vec<usize> = (0..freq.len()).filter(|&s| freq[s] > 0).collect();
+    symbols.sort_by_key(|&s| freq[s]);
+
+    // Build the tree with two queues, leaves in frequency order and internal nodes in creation order
+    let mut parent = vec![0_usize; 2 * symbols.len()];
+    let mut weight: Vec<usize> = symbols.iter().map(|&s| freq[s]).collect();
+    let (mut next_leaf, mut next_node) = (0, symbols.len());
+    let pick = |weight: &[usize], next_leaf: &mut usize, next_node: &mut usize| {
+        if *next_leaf < symbols.len() && (*next_node >= weight.len() || weight[*next_leaf] <= weight[*next_node]) {
+            *next_leaf += 1;
+            *next_leaf - 1
+        } else {
+            *next_node += 1;
+            *next_node - 1
+        }
+    };
+    for _ in 1..symbols.len() {
+        let a = pick(&weight, &mut next_leaf, &mut next_node);
+        let b = pick(&weight, &mut next_leaf, &mut next_node);
+        parent[a] = weight.len();
+        parent[b] = weight.len();
+        weight.push(weight[a] + weight[b]);
+    }
+
+    // Depth of every leaf, walking up from the root which is the last node created
+    let root = weight.len() - 1;
+    let mut depth = vec![0_usize; weight.len()];
+    for node in (0..root).rev() {
+        depth[node] = depth[parent[node]] + 1;
+    }
+
+    // Count codes per length, fold overlong codes into the limit and rebalance the Kraft sum
+    let mut count = vec![0_usize; max_length.max(depth.iter().copied().max().unwrap_or(0)) + 1];
+    for &d in &depth[..symbols.len()] {
+        count[d] += 1;
+    }
+    let overflow: usize = count[max_length + 1..].iter().sum();
+    count.truncate(max_length + 1);
+    count[max_length] += overflow;
+    let mut kraft: usize = (1..=max_length).map(|l| count[l] << (max_length - l)).sum();
+    while kraft > 1 << max_length {
+        count[max_length] -= 1;
+        if let Some(l) = (1..max_length).rev().find(|&l| count[l] > 0) {
+            count[l] -= 1;
+            count[l + 1] += 2;
+        }
+        kraft -= 1;
+    }
+
+    // Rarest symbols get the longest codes
+    let mut lengths = vec![0_u8; freq.len()];
+    let mut rarest_first = symbols.iter();
+    for l in (1..=max_length).rev() {
+        for &s in rarest_first.by_ref().take(count[l]) {
+            lengths[s] = to_u8(l);
+        }
+    }
+    lengths
+}
+
+/// Canonical Huffman codes for `lengths`, bit reversed so they can be written least significant bit first
+fn canonical_codes(lengths: &[u8]) -> Vec<u32> {
+    let mut count = [0_u32; MAX_CODE_LENGTH + 1];
+    for &l in lengths {
+        count[usize::from(l)] += 1;
+    }
+    count[0] = 0;
+    let mut next = [0_u32; MAX_CODE_LENGTH + 2];
+    for l in 1..=MAX_CODE_LENGTH {
+        next[l + 1] = (next[l] + count[l]) << 1;
+    }
+    lengths
+        .iter()
+        .map(|&l| {
+            if l == 0 {
+                return 0;
+            }
+            let l = usize::from(l);
+            let code = next[l];
+            next[l] += 1;
+            code.reverse_bits() >> (32 - l)
+        })
+        .collect()
+}
+
+fn fixed_lengths() -> (Vec<u8>, Vec<u8>) {
+    let mut lit = vec![8_u8; 288];
+    lit[144..256].fill(9);
+    lit[256..280].fill(7);
+    (lit, vec![5; 30])
+}
+
+fn length_code(length: usize) -> usize {
+    LENGTH_BASE.partition_point(|&base| usize::from(base) <= length) - 1
+}
+
+fn distance_code(distance: usize) -> usize {
+    DISTANCE_BASE.partition_point(|&base| usize::from(base) <= distance) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RenderRng;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+    use rand::{Rng, SeedableRng};
+
+    fn round_trip(data: &[u8]) {
+        let inflated = decompress_to_vec_zlib(&compress(data)).expect("stream should inflate");
+        assert_eq!(inflated, data);
+    }
+
+    #[test]
+    fn round_trips_empty_input() {
+        round_trip(&[]);
+    }
+
+    #[test]
+    fn round_trips_single_byte() {
+        round_trip(&[0x42]);
+    }
+
+    #[test]
+    fn round_trips_random_input() {
+        let mut rng = RenderRng::seed_from_u64(1);
+        let data: Vec<u8> = (0..100_000).map(|_| rng.random()).collect();
+        round_trip(&data);
+    }
+
+    #[test]
+    fn round_trips_repetitive_input() {
+        round_trip(&[7; 10_000]);
+        round_trip(&b"abcabcabd".repeat(5_000));
+    }
+
+    #[test]
+    fn round_trips_input_past_stored_block_and_window_sizes() {
+        // Mostly random with repeats reaching back most of a window, over several blocks
+        let mut rng = RenderRng::seed_from_u64(2);
+        let chunk: Vec<u8> = (0..30_000).map(|_| rng.random_range(0..16)).collect();
+        let data = chunk.repeat(10);
+        assert!(data.len() > 0xffff);
+        round_trip(&data);
+    }
+
+    #[test]
+    fn repetitive_input_compresses() {
+        assert!(compress(&vec![0; 100_000]).len() < 1_000);
+    }
+
+    #[test]
+    fn checksums_match_check_values() {
+        assert_eq!(adler32(b"123456789"), 0x091e_01de);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(&[]), 1);
+        assert_eq!(crc32(&[]), 0);
+    }
+}